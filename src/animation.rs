@@ -0,0 +1,181 @@
+//! Animation curves for driving force parameters over simulation time.
+//!
+//! Any force that exposes its parameters through [`Force::set_parameter`] can be
+//! wrapped in an [`Animated`] force, which sets each animated parameter from
+//! its [`Curve`] at the start of every step.
+
+use crate::force::{Force, ForceContext};
+use nalgebra::Vector2;
+use std::f32::consts::TAU;
+
+/// A value at a point in time.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+}
+
+/// How values between two keyframes are filled in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Hold each keyframe's value until the next one
+    Step,
+    Linear,
+    /// Smooth Catmull-Rom spline through the keyframes
+    Cubic,
+}
+
+/// Shape of a low-frequency oscillator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Square,
+    Sawtooth,
+}
+
+/// A value that changes over time.
+#[derive(Clone, Debug)]
+pub enum Curve {
+    /// Interpolated keyframes, sorted by time. Before the first and after the
+    /// last keyframe the value holds, unless `looped` repeats the keyframes forever.
+    Keyframes {
+        keys: Vec<Keyframe>,
+        interpolation: Interpolation,
+        looped: bool,
+    },
+    /// Periodic oscillation between `center - amplitude` and `center + amplitude`.
+    /// Every waveform starts its cycle rising from (or, for `Square`, jumping up
+    /// from) the center; `phase` shifts it by a fraction of a cycle.
+    Oscillator {
+        waveform: Waveform,
+        center: f32,
+        amplitude: f32,
+        frequency: f32,
+        phase: f32,
+    },
+}
+
+impl Curve {
+    /// Keyframe curve, sorting the keys by time. Keys at non-finite times are
+    /// dropped.
+    pub fn keyframes(mut keys: Vec<Keyframe>, interpolation: Interpolation, looped: bool) -> Self {
+        keys.retain(|key| key.time.is_finite());
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Curve::Keyframes { keys, interpolation, looped }
+    }
+
+    /// Sine oscillator.
+    pub fn sine(center: f32, amplitude: f32, frequency: f32) -> Self {
+        Curve::Oscillator {
+            waveform: Waveform::Sine,
+            center,
+            amplitude,
+            frequency,
+            phase: 0.0,
+        }
+    }
+
+    pub fn value_at(&self, time: f32) -> f32 {
+        match self {
+            Curve::Keyframes { keys, interpolation, looped } => keyframe_value(keys, *interpolation, *looped, time),
+            Curve::Oscillator { waveform, center, amplitude, frequency, phase } => {
+                // Position within the current cycle, in [0, 1)
+                let cycle = (time * frequency + phase).rem_euclid(1.0);
+                let wave = match waveform {
+                    Waveform::Sine => (cycle * TAU).sin(),
+                    Waveform::Triangle => 1.0 - 4.0 * ((cycle + 0.25).fract() - 0.5).abs(),
+                    Waveform::Square => if cycle < 0.5 { 1.0 } else { -1.0 },
+                    Waveform::Sawtooth => 2.0 * (cycle + 0.5).fract() - 1.0,
+                };
+                center + amplitude * wave
+            }
+        }
+    }
+}
+
+fn keyframe_value(keys: &[Keyframe], interpolation: Interpolation, looped: bool, time: f32) -> f32 {
+    let (Some(first), Some(last)) = (keys.first(), keys.last()) else {
+        return 0.0;
+    };
+    let span = last.time - first.time;
+    let time = if looped && span > 0.0 {
+        first.time + (time - first.time).rem_euclid(span)
+    } else {
+        time
+    };
+    // A NaN time, e.g. from looping over an infinite time, has no segment
+    if time.is_nan() || time <= first.time {
+        return first.value;
+    }
+    if time >= last.time {
+        return last.value;
+    }
+
+    // Segment keys[i]..keys[i + 1] containing `time`
+    let i = keys.partition_point(|key| key.time <= time) - 1;
+    let (a, b) = (keys[i], keys[i + 1]);
+    let duration = b.time - a.time;
+    let t = (time - a.time) / duration;
+    match interpolation {
+        Interpolation::Step => a.value,
+        Interpolation::Linear => a.value + (b.value - a.value) * t,
+        Interpolation::Cubic => {
+            // Catmull-Rom tangents, one-sided at the ends
+            let slope = |before: Keyframe, after: Keyframe| (after.value - before.value) / (after.time - before.time);
+            let tangent_a = slope(keys[i.saturating_sub(1)], b) * duration;
+            let tangent_b = slope(a, keys[(i + 2).min(keys.len() - 1)]) * duration;
+
+            // Cubic Hermite basis
+            let t2 = t * t;
+            let t3 = t2 * t;
+            (2.0 * t3 - 3.0 * t2 + 1.0) * a.value
+                + (t3 - 2.0 * t2 + t) * tangent_a
+                + (-2.0 * t3 + 3.0 * t2) * b.value
+                + (t3 - t2) * tangent_b
+        }
+    }
+}
+
+/// Wraps a force and drives some of its parameters with curves.
+pub struct Animated {
+    force: Box<dyn Force>,
+    tracks: Vec<(String, Curve)>,
+}
+
+impl Animated {
+    pub fn new(force: Box<dyn Force>) -> Self {
+        Self {
+            force,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Drives `parameter` of the wrapped force with `curve`, starting from its
+    /// value at time zero. Returns `false`, leaving the force unchanged, if the
+    /// force has no such parameter.
+    pub fn animate(&mut self, parameter: &str, curve: Curve) -> bool {
+        if !self.force.set_parameter(parameter, curve.value_at(0.0)) {
+            return false;
+        }
+        self.tracks.push((parameter.to_string(), curve));
+        true
+    }
+}
+
+impl Force for Animated {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        self.force.apply(ctx)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        self.force.set_parameter(name, value)
+    }
+
+    fn advance(&mut self, time: f32) {
+        for (parameter, curve) in &self.tracks {
+            self.force.set_parameter(parameter, curve.value_at(time));
+        }
+        self.force.advance(time);
+    }
+}
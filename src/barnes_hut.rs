@@ -0,0 +1,177 @@
+use nalgebra::Vector2;
+
+// Deeper than this, remaining points are coincident for all practical purposes
+// and are kept together in one leaf.
+const MAX_DEPTH: usize = 32;
+
+/// Per-point quantities summed over every tree node, e.g. particle count and
+/// total mass. `weight` decides where a node's centroid lies: mass for
+/// gravity-like laws, 1.0 for laws that only count neighbours.
+pub trait Aggregate: Copy + Default {
+    fn add(&mut self, other: &Self);
+    fn weight(&self) -> f32;
+}
+
+struct Node<T> {
+    center: Vector2<f32>,    // Center of the node's square
+    size: f32,               // Side length of the node's square
+    centroid: Vector2<f32>,  // Weighted mean position of the points below
+    total: T,                // Sum of the aggregates of the points below
+    children: Option<[usize; 4]>,
+    points: (usize, usize),  // Range of `order` holding the points below
+}
+
+/// Barnes-Hut quadtree for approximating long-range pair interactions.
+///
+/// Each node summarizes the points below it by their summed [`Aggregate`]
+/// and weighted centroid. When summing the contributions on a point, nodes that
+/// look small from it (`size / distance < theta`) are treated as a single point,
+/// which brings the cost of a full pass down from O(n²) to O(n log n).
+/// `theta = 0` gives the exact sum; around 0.5 is a common accuracy/speed trade-off.
+pub struct BarnesHutTree<T> {
+    nodes: Vec<Node<T>>,
+    order: Vec<usize>,
+    positions: Vec<Vector2<f32>>,
+    aggregates: Vec<T>,
+}
+
+impl<T: Aggregate> BarnesHutTree<T> {
+    /// Builds a tree over `positions`, with one aggregate per point.
+    pub fn new(positions: &[Vector2<f32>], aggregates: &[T]) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            order: (0..positions.len()).collect(),
+            positions: positions.to_vec(),
+            aggregates: aggregates.to_vec(),
+        };
+        if positions.is_empty() {
+            return tree;
+        }
+
+        // Square root node around all points
+        let min = positions.iter().fold(positions[0], |a, b| a.inf(b));
+        let max = positions.iter().fold(positions[0], |a, b| a.sup(b));
+        let size = (max - min).max().max(1.0);
+        tree.nodes.push(Self::empty_node((min + max) * 0.5, size, (0, positions.len())));
+        tree.build(0, 0);
+        tree
+    }
+
+    fn empty_node(center: Vector2<f32>, size: f32, points: (usize, usize)) -> Node<T> {
+        Node {
+            center,
+            size,
+            centroid: center,
+            total: T::default(),
+            children: None,
+            points,
+        }
+    }
+
+    fn build(&mut self, node: usize, depth: usize) {
+        let (start, end) = self.nodes[node].points;
+        let center = self.nodes[node].center;
+        let size = self.nodes[node].size;
+
+        if end - start > 1 && depth < MAX_DEPTH {
+            // Partition the node's points into quadrants: split on y, then on x
+            let points = &mut self.order[start..end];
+            let positions = &self.positions;
+            let mid = start + partition(points, |i| positions[i].y < center.y);
+            let top = start + partition(&mut self.order[start..mid], |i| positions[i].x < center.x);
+            let bottom = mid + partition(&mut self.order[mid..end], |i| positions[i].x < center.x);
+
+            let quarter = size * 0.25;
+            let quadrants = [
+                (Vector2::new(center.x - quarter, center.y - quarter), (start, top)),
+                (Vector2::new(center.x + quarter, center.y - quarter), (top, mid)),
+                (Vector2::new(center.x - quarter, center.y + quarter), (mid, bottom)),
+                (Vector2::new(center.x + quarter, center.y + quarter), (bottom, end)),
+            ];
+
+            let mut children = [0; 4];
+            for (child, (child_center, points)) in children.iter_mut().zip(quadrants) {
+                *child = self.nodes.len();
+                self.nodes.push(Self::empty_node(child_center, size * 0.5, points));
+                self.build(*child, depth + 1);
+            }
+            self.nodes[node].children = Some(children);
+        }
+
+        // Aggregate the node's points
+        let mut total = T::default();
+        let mut weighted_sum = Vector2::new(0.0, 0.0);
+        for &i in &self.order[start..end] {
+            total.add(&self.aggregates[i]);
+            weighted_sum += self.positions[i] * self.aggregates[i].weight();
+        }
+        let node = &mut self.nodes[node];
+        node.total = total;
+        if total.weight() != 0.0 {
+            node.centroid = weighted_sum / total.weight();
+        }
+    }
+
+    /// Sums the contributions of every point except `index` on a point at
+    /// `position`. Points that are close, or far but not aggregated, contribute
+    /// `near(j)`; distant nodes contribute `far(offset, total)`, where `offset`
+    /// runs from `position` to the node's centroid.
+    pub fn accumulate(
+        &self,
+        index: usize,
+        position: Vector2<f32>,
+        theta: f32,
+        near: &impl Fn(usize) -> Vector2<f32>,
+        far: &impl Fn(Vector2<f32>, &T) -> Vector2<f32>,
+    ) -> Vector2<f32> {
+        if self.nodes.is_empty() {
+            return Vector2::new(0.0, 0.0);
+        }
+        self.accumulate_node(0, index, position, theta, near, far)
+    }
+
+    fn accumulate_node(
+        &self,
+        node: usize,
+        index: usize,
+        position: Vector2<f32>,
+        theta: f32,
+        near: &impl Fn(usize) -> Vector2<f32>,
+        far: &impl Fn(Vector2<f32>, &T) -> Vector2<f32>,
+    ) -> Vector2<f32> {
+        let node = &self.nodes[node];
+        let (start, end) = node.points;
+        if start == end {
+            return Vector2::new(0.0, 0.0);
+        }
+
+        // Far enough away (and not containing the point itself): treat as one point
+        let offset = node.centroid - position;
+        let outside = (position - node.center).abs().max() > node.size * 0.5;
+        if outside && end - start > 1 && node.size < theta * offset.norm() {
+            return far(offset, &node.total);
+        }
+
+        match node.children {
+            Some(children) => children.iter()
+                .map(|&child| self.accumulate_node(child, index, position, theta, near, far))
+                .sum(),
+            None => self.order[start..end].iter()
+                .filter(|&&i| i != index)
+                .map(|&i| near(i))
+                .sum(),
+        }
+    }
+}
+
+// Moves the items matching `predicate` to the front, returning how many there are
+fn partition(items: &mut [usize], predicate: impl Fn(usize) -> bool) -> usize {
+    let mut split = 0;
+    for i in 0..items.len() {
+        if predicate(items[i]) {
+            items.swap(split, i);
+            split += 1;
+        }
+    }
+    split
+}
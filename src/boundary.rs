@@ -0,0 +1,108 @@
+//! Walls of the simulation bounds.
+//!
+//! Each edge of the bounds has its own [`BoundaryPolicy`], so e.g. a floor can
+//! be sticky while particles leave freely through the sides.
+
+use nalgebra::Vector2;
+
+/// What happens to a particle that reaches an edge of the bounds.
+///
+/// Variants are ordered by precedence: a particle in a corner gets the
+/// greatest of the two policies reported for it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BoundaryPolicy {
+    /// No wall: particles pass through and carry on outside the bounds
+    Open,
+    /// Bounce off the wall, losing speed according to the particle's restitution
+    #[default]
+    Reflect,
+    /// Stop dead against the wall
+    Stick,
+    /// Remove the particle, as if the wall absorbed it
+    Kill,
+}
+
+/// A particle reaching a wall, as reported by [`Boundaries::apply`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+    pub policy: BoundaryPolicy,
+    /// Speed into the wall on arrival
+    pub speed: f32,
+}
+
+/// Policies for the four edges of the bounds. Defaults to reflecting everywhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Boundaries {
+    pub left: BoundaryPolicy,
+    pub right: BoundaryPolicy,
+    pub top: BoundaryPolicy,
+    pub bottom: BoundaryPolicy,
+    /// Slowest arrival that counts as hitting a wall, e.g. for sub-emitters, so
+    /// particles resting against a wall don't keep hitting it
+    pub min_impact_speed: f32,
+}
+
+impl Boundaries {
+    /// The same policy on every edge.
+    pub fn all(policy: BoundaryPolicy) -> Self {
+        Self {
+            left: policy,
+            right: policy,
+            top: policy,
+            bottom: policy,
+            min_impact_speed: 10.0,
+        }
+    }
+
+    /// Keeps a particle at `position` with `radius` inside `bounds` (width and
+    /// height) according to the edge policies. Returns the wall it reached, if
+    /// any; removing a particle that reached a `Kill` wall is up to the caller,
+    /// which leaves it where it was.
+    pub fn apply(
+        &self,
+        position: &mut Vector2<f32>,
+        velocity: &mut Vector2<f32>,
+        radius: f32,
+        restitution: f32,
+        bounds: Vector2<f32>,
+    ) -> Option<WallHit> {
+        let arrival = *velocity;
+        let mut hit: Option<WallHit> = None;
+        for (axis, low, high) in [(0, self.left, self.right), (1, self.top, self.bottom)] {
+            let (policy, contact, speed) = if position[axis] - radius < 0.0 {
+                (low, radius, -arrival[axis])
+            } else if position[axis] + radius > bounds[axis] {
+                (high, bounds[axis] - radius, arrival[axis])
+            } else {
+                continue;
+            };
+            match policy {
+                BoundaryPolicy::Open => continue,
+                BoundaryPolicy::Reflect => {
+                    position[axis] = contact;
+                    velocity[axis] = -velocity[axis] * restitution;
+                }
+                BoundaryPolicy::Stick => {
+                    position[axis] = contact;
+                    *velocity = Vector2::new(0.0, 0.0);
+                }
+                BoundaryPolicy::Kill => {}
+            }
+            let speed = speed.max(0.0);
+            hit = Some(match hit {
+                Some(other) => WallHit {
+                    policy: policy.max(other.policy),
+                    speed: speed.max(other.speed),
+                },
+                None => WallHit { policy, speed },
+            });
+        }
+        hit
+    }
+}
+
+impl Default for Boundaries {
+    fn default() -> Self {
+        Self::all(BoundaryPolicy::Reflect)
+    }
+}
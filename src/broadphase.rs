@@ -0,0 +1,331 @@
+use nalgebra::Vector2;
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl Aabb {
+    // Bounding box of a circle
+    pub fn around(center: Vector2<f32>, radius: f32) -> Self {
+        Self {
+            min: center - Vector2::new(radius, radius),
+            max: center + Vector2::new(radius, radius),
+        }
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x &&
+        self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x && other.max.x <= self.max.x &&
+        self.min.y <= other.min.y && other.max.y <= self.max.y
+    }
+
+    // Smallest box enclosing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+}
+
+/// Broad-phase collision detection: cheaply narrows all particle pairs down to
+/// the ones whose bounding boxes overlap, so the exact (narrow-phase) test only
+/// runs on likely collisions.
+pub trait BroadPhase: Send {
+    /// Replaces the contents of `pairs` with every pair `(i, j)`, `i < j`, whose
+    /// boxes overlap. Each pair is reported once, and the order depends only on
+    /// the input so simulations stay deterministic.
+    fn find_pairs(&mut self, boxes: &[Aabb], pairs: &mut Vec<(usize, usize)>);
+}
+
+/// Uniform grid (spatial hash). Each box is registered in every cell it touches
+/// and only boxes sharing a cell are tested. Works best when `cell_size` is
+/// about the diameter of a typical particle. Boxes covering more than
+/// [`UniformGrid::MAX_CELLS_PER_BOX`] cells are kept out of the grid and
+/// tested against every other box instead.
+pub struct UniformGrid {
+    pub cell_size: f32,
+    entries: Vec<((i32, i32), usize)>,
+    oversized: Vec<usize>,
+}
+
+impl UniformGrid {
+    /// Most cells a box is registered in, so a single huge particle can't
+    /// flood the grid.
+    pub const MAX_CELLS_PER_BOX: i64 = 64;
+
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            entries: Vec::new(),
+            oversized: Vec::new(),
+        }
+    }
+
+    fn cell_of(&self, point: Vector2<f32>) -> (i32, i32) {
+        ((point.x / self.cell_size).floor() as i32, (point.y / self.cell_size).floor() as i32)
+    }
+}
+
+impl BroadPhase for UniformGrid {
+    fn find_pairs(&mut self, boxes: &[Aabb], pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+
+        // Register every box in each cell it covers
+        self.entries.clear();
+        self.oversized.clear();
+        for (index, aabb) in boxes.iter().enumerate() {
+            let (x0, y0) = self.cell_of(aabb.min);
+            let (x1, y1) = self.cell_of(aabb.max);
+            let cells = (i64::from(x1) - i64::from(x0) + 1).saturating_mul(i64::from(y1) - i64::from(y0) + 1);
+            if cells > Self::MAX_CELLS_PER_BOX {
+                self.oversized.push(index);
+                continue;
+            }
+            for y in y0..=y1 {
+                for x in x0..=x1 {
+                    self.entries.push(((y, x), index));
+                }
+            }
+        }
+
+        // Sorting groups the entries of each cell together
+        self.entries.sort_unstable();
+
+        let mut start = 0;
+        while start < self.entries.len() {
+            let cell = self.entries[start].0;
+            let end = start + self.entries[start..].iter().take_while(|entry| entry.0 == cell).count();
+
+            for a in start..end {
+                for b in (a + 1)..end {
+                    let i = self.entries[a].1;
+                    let j = self.entries[b].1;
+                    if !boxes[i].overlaps(&boxes[j]) {
+                        continue;
+                    }
+                    // Boxes spanning several cells share more than one of them;
+                    // only report the pair from the first cell they share
+                    let (ix, iy) = self.cell_of(boxes[i].min);
+                    let (jx, jy) = self.cell_of(boxes[j].min);
+                    if (iy.max(jy), ix.max(jx)) == cell {
+                        pairs.push((i, j));
+                    }
+                }
+            }
+
+            start = end;
+        }
+
+        // Oversized boxes against every other box; a pair of oversized boxes
+        // is reported from the first of the two
+        for &i in &self.oversized {
+            for (j, aabb) in boxes.iter().enumerate() {
+                if j == i || (j < i && self.oversized.binary_search(&j).is_ok()) || !boxes[i].overlaps(aabb) {
+                    continue;
+                }
+                pairs.push((i.min(j), i.max(j)));
+            }
+        }
+    }
+}
+
+/// Sweep and prune along the x axis. The sort order is kept between calls, so
+/// with the small frame-to-frame motion of a simulation re-sorting is nearly linear.
+pub struct SweepAndPrune {
+    order: Vec<usize>,
+    active: Vec<usize>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        Self {
+            order: Vec::new(),
+            active: Vec::new(),
+        }
+    }
+}
+
+impl Default for SweepAndPrune {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BroadPhase for SweepAndPrune {
+    fn find_pairs(&mut self, boxes: &[Aabb], pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+
+        if self.order.len() != boxes.len() {
+            self.order = (0..boxes.len()).collect();
+        }
+        // Stable sort, which is close to linear on nearly sorted input
+        self.order.sort_by(|&a, &b| boxes[a].min.x.total_cmp(&boxes[b].min.x));
+
+        self.active.clear();
+        for &i in &self.order {
+            // Drop boxes that end before this one starts; they can't overlap anything later either
+            let min_x = boxes[i].min.x;
+            self.active.retain(|&j| boxes[j].max.x >= min_x);
+
+            for &j in &self.active {
+                if boxes[i].overlaps(&boxes[j]) {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+            self.active.push(i);
+        }
+    }
+}
+
+struct QuadNode {
+    bounds: Aabb,
+    children: Option<[usize; 4]>,
+    items: Vec<usize>,
+    depth: usize,
+}
+
+/// Region quadtree rebuilt on every call. Boxes are stored in the deepest node
+/// that fully contains them, so large and small particles can be mixed freely.
+pub struct QuadTree {
+    pub max_depth: usize,
+    pub node_capacity: usize,
+    nodes: Vec<QuadNode>,
+    stack: Vec<usize>,
+}
+
+impl QuadTree {
+    pub fn new(max_depth: usize, node_capacity: usize) -> Self {
+        Self {
+            max_depth,
+            node_capacity,
+            nodes: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, boxes: &[Aabb], index: usize) {
+        let mut node = 0;
+        while let Some(children) = self.nodes[node].children {
+            match children.iter().find(|&&child| self.nodes[child].bounds.contains(&boxes[index])) {
+                Some(&child) => node = child,
+                None => break,
+            }
+        }
+
+        self.nodes[node].items.push(index);
+
+        let leaf = &self.nodes[node];
+        if leaf.children.is_none() && leaf.items.len() > self.node_capacity && leaf.depth < self.max_depth {
+            self.split(boxes, node);
+        }
+    }
+
+    fn split(&mut self, boxes: &[Aabb], node: usize) {
+        let bounds = self.nodes[node].bounds;
+        let depth = self.nodes[node].depth + 1;
+        let center = (bounds.min + bounds.max) * 0.5;
+
+        let quadrants = [
+            Aabb { min: bounds.min, max: center },
+            Aabb { min: Vector2::new(center.x, bounds.min.y), max: Vector2::new(bounds.max.x, center.y) },
+            Aabb { min: Vector2::new(bounds.min.x, center.y), max: Vector2::new(center.x, bounds.max.y) },
+            Aabb { min: center, max: bounds.max },
+        ];
+        let first = self.nodes.len();
+        for bounds in quadrants {
+            self.nodes.push(QuadNode { bounds, children: None, items: Vec::new(), depth });
+        }
+        self.nodes[node].children = Some([first, first + 1, first + 2, first + 3]);
+
+        // Push items down into the children that fully contain them
+        let items = std::mem::take(&mut self.nodes[node].items);
+        for index in items {
+            match (first..first + 4).find(|&child| self.nodes[child].bounds.contains(&boxes[index])) {
+                Some(child) => self.nodes[child].items.push(index),
+                None => self.nodes[node].items.push(index),
+            }
+        }
+    }
+}
+
+impl Default for QuadTree {
+    fn default() -> Self {
+        Self::new(8, 8)
+    }
+}
+
+impl BroadPhase for QuadTree {
+    fn find_pairs(&mut self, boxes: &[Aabb], pairs: &mut Vec<(usize, usize)>) {
+        pairs.clear();
+        self.nodes.clear();
+
+        let Some(root) = boxes.iter().copied().reduce(|a, b| a.union(&b)) else {
+            return;
+        };
+        self.nodes.push(QuadNode { bounds: root, children: None, items: Vec::new(), depth: 0 });
+        for index in 0..boxes.len() {
+            self.insert(boxes, index);
+        }
+
+        // Query the tree with every box, keeping only pairs with a higher index
+        for (i, aabb) in boxes.iter().enumerate() {
+            self.stack.clear();
+            self.stack.push(0);
+            while let Some(node) = self.stack.pop() {
+                let node = &self.nodes[node];
+                if !node.bounds.overlaps(aabb) {
+                    continue;
+                }
+                for &j in &node.items {
+                    if j > i && aabb.overlaps(&boxes[j]) {
+                        pairs.push((i, j));
+                    }
+                }
+                if let Some(children) = node.children {
+                    self.stack.extend_from_slice(&children);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every overlapping pair, by testing all of them
+    fn brute_force(boxes: &[Aabb]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..boxes.len() {
+            for j in (i + 1)..boxes.len() {
+                if boxes[i].overlaps(&boxes[j]) {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn uniform_grid_handles_huge_boxes() {
+        let mut boxes: Vec<Aabb> = (0..20)
+            .map(|i| Aabb::around(Vector2::new((i % 5) as f32 * 7.0, (i / 5) as f32 * 7.0), 4.0))
+            .collect();
+        boxes.push(Aabb::around(Vector2::new(10.0, 10.0), 1e9));
+        boxes.push(Aabb::around(Vector2::new(0.0, 0.0), 30.0));
+        boxes.push(Aabb::around(Vector2::new(1000.0, 1000.0), 100.0));
+
+        let mut pairs = Vec::new();
+        UniformGrid::new(8.0).find_pairs(&boxes, &mut pairs);
+        pairs.sort_unstable();
+        assert_eq!(pairs, brute_force(&boxes));
+    }
+}
@@ -0,0 +1,148 @@
+use particle_system::renderer::MAX_SIZE;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Real-time 2D particle simulation
+
+Usage: particle_system [OPTIONS]
+
+Options:
+      --width <PIXELS>     Window and simulation width [default: 800]
+      --height <PIXELS>    Window and simulation height [default: 600]
+      --particles <COUNT>  Number of particles in the built-in scene [default: 50]
+      --seed <SEED>        Seed for the initial particles [default: random]
+      --scene <FILE>       Load a TOML scene file instead of the built-in scene
+      --headless           Run without opening a window (requires --frames)
+      --frames <COUNT>     Stop after this many frames
+      --output <DIR>       Write every rendered frame to DIR as a PPM image
+  -h, --help               Print this help
+";
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub particles: Option<usize>,
+    pub seed: Option<u64>,
+    pub scene: Option<PathBuf>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+    pub output: Option<PathBuf>,
+}
+
+pub enum Command {
+    Run(Options),
+    Help,
+}
+
+/// Parses the command-line arguments (without the program name).
+/// Accepts both `--flag value` and `--flag=value`.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline_value.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("`{}` requires a value", flag))
+        };
+
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--width" => options.width = Some(size(&flag, &value()?)?),
+            "--height" => options.height = Some(size(&flag, &value()?)?),
+            "--particles" => options.particles = Some(number(&flag, &value()?)?),
+            "--seed" => options.seed = Some(number(&flag, &value()?)?),
+            "--scene" => options.scene = Some(PathBuf::from(value()?)),
+            "--headless" if inline_value.is_some() => return Err(format!("`{}` does not take a value", flag)),
+            "--headless" => options.headless = true,
+            "--frames" => options.frames = Some(positive(&flag, &value()?)?),
+            "--output" => options.output = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unexpected argument `{}`", flag)),
+        }
+    }
+
+    if let (Some(width), Some(height)) = (options.width, options.height) {
+        if width.checked_mul(height).is_none() {
+            return Err(format!("a {}x{} window is too large", width, height));
+        }
+    }
+    if options.headless && options.frames.is_none() {
+        return Err("`--headless` requires `--frames`".to_string());
+    }
+    if options.scene.is_some() && options.particles.is_some() {
+        return Err("`--particles` only applies to the built-in scene; set population counts in the scene file".to_string());
+    }
+
+    Ok(Command::Run(options))
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value `{}` for `{}`: expected a non-negative integer", value, flag))
+}
+
+fn positive<T: std::str::FromStr + Default + PartialEq>(flag: &str, value: &str) -> Result<T, String> {
+    let parsed: T = number(flag, value)?;
+    if parsed == T::default() {
+        return Err(format!("invalid value `{}` for `{}`: must be greater than zero", value, flag));
+    }
+    Ok(parsed)
+}
+
+fn size(flag: &str, value: &str) -> Result<usize, String> {
+    let parsed = positive(flag, value)?;
+    if parsed > MAX_SIZE {
+        return Err(format!("invalid value `{}` for `{}`: must be at most {}", value, flag, MAX_SIZE));
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Options, String> {
+        match parse(args.iter().map(|arg| arg.to_string()))? {
+            Command::Run(options) => Ok(options),
+            Command::Help => Err("help".to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_separate_and_inline_values() {
+        let options = run(&["--width", "640", "--height=480", "--seed=7", "--headless", "--frames", "10"]).unwrap();
+        assert_eq!(options.width, Some(640));
+        assert_eq!(options.height, Some(480));
+        assert_eq!(options.seed, Some(7));
+        assert!(options.headless);
+        assert_eq!(options.frames, Some(10));
+        assert_eq!(options.scene, None);
+    }
+
+    #[test]
+    fn help_wins_over_other_flags() {
+        assert!(matches!(parse(["--width".to_string(), "640".to_string(), "-h".to_string()]), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for args in [
+            &["--width", "0"][..],
+            &["--height", "16385"],
+            &["--width", "18446744073709551615"],
+            &["--width", "wide"],
+            &["--width"],
+            &["--headless"],
+            &["--headless=yes", "--frames", "1"],
+            &["--scene", "a.toml", "--particles", "10"],
+            &["--verbose"],
+        ] {
+            assert!(run(args).is_err(), "{:?} should be rejected", args);
+        }
+    }
+}
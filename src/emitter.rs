@@ -0,0 +1,218 @@
+use crate::group::GroupMask;
+use crate::particle::Particle;
+use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::f32::consts::TAU;
+use std::ops::RangeInclusive;
+
+/// Area particles are spawned in, relative to the emitter's position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    /// Segment from the emitter's position to `position + offset`
+    Line { offset: Vector2<f32> },
+    /// Filled disc centered on the emitter
+    Circle { radius: f32 },
+    /// Rectangle centered on the emitter
+    Box { width: f32, height: f32 },
+}
+
+/// A one-off or repeating burst of `count` particles at `time` seconds.
+#[derive(Clone, Copy, Debug)]
+pub struct Burst {
+    pub time: f32,
+    pub count: usize,
+    /// Repeat every `interval` seconds after the first burst
+    pub interval: Option<f32>,
+}
+
+/// Spawns new particles while the simulation runs, continuously at `rate`
+/// particles per second and in bursts.
+///
+/// Particles leave in a cone around `angle` (degrees clockwise from +x; y
+/// points down, so -90 is straight up) that is `spread` degrees wide, with a
+/// speed, mass and radius drawn uniformly from their ranges. The radius
+/// defaults to twice the mass, and the color is picked from `colors`, or
+/// random when there are none. Particles live forever unless given a
+/// `lifetime` range in seconds.
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    pub position: Vector2<f32>,
+    pub shape: EmitterShape,
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    pub angle: f32,
+    pub spread: f32,
+    pub speed: RangeInclusive<f32>,
+    pub mass: RangeInclusive<f32>,
+    pub radius: Option<RangeInclusive<f32>>,
+    pub colors: Vec<u32>,
+    pub group: u8,
+    pub lifetime: Option<RangeInclusive<f32>>,
+    pending: f32, // Fraction of a particle owed by the continuous rate
+}
+
+impl Emitter {
+    /// A point emitter that spawns nothing until given a rate or bursts.
+    pub fn new(position: Vector2<f32>) -> Self {
+        Self {
+            position,
+            shape: EmitterShape::Point,
+            rate: 0.0,
+            bursts: Vec::new(),
+            angle: -90.0,
+            spread: 0.0,
+            speed: 0.0..=0.0,
+            mass: 1.0..=1.0,
+            radius: None,
+            colors: Vec::new(),
+            group: 0,
+            lifetime: None,
+            pending: 0.0,
+        }
+    }
+
+    /// Number of particles due in the step from `time` to `time + dt`.
+    pub fn due(&mut self, time: f32, dt: f32) -> usize {
+        self.pending += self.rate * dt;
+        let continuous = self.pending.floor();
+        self.pending -= continuous;
+
+        let mut count = continuous as usize;
+        for burst in &self.bursts {
+            count += burst.count * burst_repeats(burst, time, time + dt);
+        }
+        count
+    }
+
+    /// Draws one new particle.
+    pub fn spawn(&self, rng: &mut StdRng) -> Particle {
+        let position = self.position + match self.shape {
+            EmitterShape::Point => Vector2::new(0.0, 0.0),
+            EmitterShape::Line { offset } => offset * rng.gen_range(0.0..=1.0),
+            EmitterShape::Circle { radius } => {
+                // Square root of a uniform sample keeps the disc evenly filled
+                let distance = radius * rng.gen_range(0.0f32..=1.0).sqrt();
+                let angle = rng.gen_range(0.0..TAU);
+                Vector2::new(angle.cos(), angle.sin()) * distance
+            }
+            EmitterShape::Box { width, height } => Vector2::new(
+                width * rng.gen_range(-0.5..=0.5),
+                height * rng.gen_range(-0.5..=0.5),
+            ),
+        };
+
+        let half_spread = self.spread * 0.5;
+        let angle = (self.angle + rng.gen_range(-half_spread..=half_spread)).to_radians();
+        let speed = rng.gen_range(self.speed.clone());
+        let mass = rng.gen_range(self.mass.clone());
+        let radius = match &self.radius {
+            Some(radius) => rng.gen_range(radius.clone()),
+            None => mass * 2.0,
+        };
+        let color = if self.colors.is_empty() {
+            rng.gen_range(0..0x1000000)
+        } else {
+            self.colors[rng.gen_range(0..self.colors.len())]
+        };
+
+        let mut particle = Particle::new(position.x, position.y, mass, radius, color);
+        particle.velocity = Vector2::new(angle.cos(), angle.sin()) * speed;
+        particle.group = self.group;
+        if let Some(lifetime) = &self.lifetime {
+            particle.lifetime = rng.gen_range(lifetime.clone());
+        }
+        particle
+    }
+}
+
+/// Event that makes a sub-emitter fire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// The particle reached its lifetime
+    Death,
+    /// The particle hit a wall of the simulation bounds at
+    /// [`Boundaries::min_impact_speed`](crate::boundary::Boundaries::min_impact_speed)
+    /// or faster, or was killed by one
+    Boundary,
+    /// The particle came into contact with another particle of its own or an
+    /// earlier generation; a contact fires once, however long it lasts
+    Collision,
+}
+
+/// Spawns `count` children from a particle whenever `trigger` happens to it,
+/// e.g. sparks when a firework shell dies or droplets when a drop splashes.
+///
+/// Children are drawn from `emitter` as if it sat on the parent: its position
+/// is an offset from the parent's, and `inherit_velocity` is the fraction of
+/// the parent's velocity added to each child's. The emitter's rate and bursts
+/// are not used.
+#[derive(Clone, Debug)]
+pub struct SubEmitter {
+    pub trigger: Trigger,
+    /// Groups whose particles fire the sub-emitter
+    pub parents: GroupMask,
+    pub count: usize,
+    pub inherit_velocity: f32,
+    pub emitter: Emitter,
+}
+
+impl SubEmitter {
+    /// Fires one child per trigger from particles of any group, spawned at the
+    /// parent and carried along with its full velocity.
+    pub fn new(trigger: Trigger, emitter: Emitter) -> Self {
+        Self {
+            trigger,
+            parents: GroupMask::ALL,
+            count: 1,
+            inherit_velocity: 1.0,
+            emitter,
+        }
+    }
+
+    /// Draws one child of `parent`.
+    pub fn spawn(&self, parent: &Particle, rng: &mut StdRng) -> Particle {
+        let mut child = self.emitter.spawn(rng);
+        child.position += parent.position;
+        child.velocity += parent.velocity * self.inherit_velocity;
+        child.generation = parent.generation.saturating_add(1);
+        child
+    }
+}
+
+// How many repeats of `burst` fall within [start, end)
+fn burst_repeats(burst: &Burst, start: f32, end: f32) -> usize {
+    match burst.interval {
+        Some(interval) if interval > 0.0 => {
+            // Repeats k >= 0 at burst.time + k * interval
+            let first = ((start - burst.time) / interval).ceil().max(0.0);
+            let last = ((end - burst.time) / interval).ceil().max(0.0);
+            (last - first) as usize
+        }
+        _ => usize::from(start <= burst.time && burst.time < end),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_off_burst_fires_in_the_step_containing_it() {
+        let burst = Burst { time: 1.0, count: 5, interval: None };
+        assert_eq!(burst_repeats(&burst, 0.5, 1.0), 0);
+        assert_eq!(burst_repeats(&burst, 1.0, 1.5), 1);
+        assert_eq!(burst_repeats(&burst, 1.5, 2.0), 0);
+    }
+
+    #[test]
+    fn repeating_burst_counts_every_repeat_in_the_window() {
+        let burst = Burst { time: 1.0, count: 5, interval: Some(0.5) };
+        assert_eq!(burst_repeats(&burst, 0.0, 0.9), 0);
+        // Repeats at 1.0, 1.5 and 2.0; 2.5 falls just past the end
+        assert_eq!(burst_repeats(&burst, 0.9, 2.5), 3);
+        assert_eq!(burst_repeats(&burst, 2.5, 2.6), 1);
+        assert_eq!(burst_repeats(&burst, 2.6, 2.9), 0);
+    }
+}
@@ -0,0 +1,230 @@
+//! Vector grids for authored flow fields.
+//!
+//! A grid can be read from a text file:
+//!
+//! ```text
+//! # width height, then one "x y" vector per node, row by row from the top
+//! 3 2
+//! 1 0   0 1   -1 0
+//! 0 -1  1 1    0 0
+//! ```
+//!
+//! or from a PPM image (`.ppm`), whose red and green channels map 0..255 onto
+//! x and y components in -1..1.
+
+use nalgebra::Vector2;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum GridError {
+    Io(std::io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::Io(e) => write!(f, "could not read grid: {}", e),
+            GridError::Invalid(message) => write!(f, "invalid grid: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GridError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GridError::Io(e) => Some(e),
+            GridError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GridError {
+    fn from(e: std::io::Error) -> Self {
+        GridError::Io(e)
+    }
+}
+
+/// A rectangular grid of vectors, sampled with bilinear interpolation.
+#[derive(Clone, Debug)]
+pub struct VectorGrid {
+    width: usize,
+    height: usize,
+    vectors: Vec<Vector2<f32>>, // Row-major, top row first
+}
+
+impl VectorGrid {
+    /// Panics unless there are `width * height` vectors and both sizes are non-zero.
+    pub fn new(width: usize, height: usize, vectors: Vec<Vector2<f32>>) -> Self {
+        assert!(width > 0 && height > 0, "vector grid must not be empty");
+        assert_eq!(vectors.len(), width * height, "vector grid size mismatch");
+        Self { width, height, vectors }
+    }
+
+    /// Loads a grid from a PPM image if the extension is `.ppm`, otherwise from a text grid file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GridError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)?;
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ppm")) {
+            Self::from_ppm(&bytes)
+        } else {
+            let text = std::str::from_utf8(&bytes)
+                .map_err(|_| GridError::Invalid("grid file is not valid UTF-8".to_string()))?;
+            Self::from_text(text)
+        }
+    }
+
+    /// Parses the text grid format.
+    pub fn from_text(text: &str) -> Result<Self, GridError> {
+        let mut numbers = text.lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(str::split_whitespace);
+        let mut size = || -> Result<usize, GridError> {
+            let token = numbers.next().ok_or_else(|| GridError::Invalid("missing grid size".to_string()))?;
+            match token.parse() {
+                Ok(size) if size > 0 => Ok(size),
+                _ => Err(GridError::Invalid(format!("invalid grid size `{}`", token))),
+            }
+        };
+        let (width, height) = (size()?, size()?);
+        let expected = grid_len(width, height)?;
+
+        let components = numbers
+            .map(|token| token.parse::<f32>().map_err(|_| GridError::Invalid(format!("invalid number `{}`", token))))
+            .collect::<Result<Vec<f32>, GridError>>()?;
+        if expected.checked_mul(2) != Some(components.len()) {
+            return Err(GridError::Invalid(format!(
+                "expected {} vectors for a {}x{} grid, found {} numbers",
+                expected, width, height, components.len()
+            )));
+        }
+
+        let vectors = components.chunks(2).map(|pair| Vector2::new(pair[0], pair[1])).collect();
+        Ok(Self::new(width, height, vectors))
+    }
+
+    /// Decodes a binary (P6) PPM image with 8-bit channels.
+    pub fn from_ppm(bytes: &[u8]) -> Result<Self, GridError> {
+        // Header: magic, width, height, maxval, separated by whitespace and comments
+        let mut position = 0;
+        let mut fields = Vec::new();
+        while fields.len() < 4 {
+            while position < bytes.len() && (bytes[position].is_ascii_whitespace() || bytes[position] == b'#') {
+                if bytes[position] == b'#' {
+                    while position < bytes.len() && bytes[position] != b'\n' {
+                        position += 1;
+                    }
+                } else {
+                    position += 1;
+                }
+            }
+            let start = position;
+            while position < bytes.len() && !bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            if start == position {
+                return Err(GridError::Invalid("truncated PPM header".to_string()));
+            }
+            fields.push(String::from_utf8_lossy(&bytes[start..position]).into_owned());
+        }
+        // Exactly one whitespace byte separates the header from the pixels
+        position += 1;
+
+        if fields[0] != "P6" {
+            return Err(GridError::Invalid("only binary (P6) PPM images are supported".to_string()));
+        }
+        let number = |field: &str| field.parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| GridError::Invalid(format!("invalid PPM header value `{}`", field)));
+        let (width, height, max_value) = (number(&fields[1])?, number(&fields[2])?, number(&fields[3])?);
+        if max_value > 255 {
+            return Err(GridError::Invalid("16-bit PPM images are not supported".to_string()));
+        }
+
+        let end = grid_len(width, height)?
+            .checked_mul(3)
+            .and_then(|size| size.checked_add(position))
+            .ok_or_else(|| GridError::Invalid(format!("PPM image size {}x{} is too large", width, height)))?;
+        let pixels = bytes.get(position..end)
+            .ok_or_else(|| GridError::Invalid("PPM image is truncated".to_string()))?;
+        let channel = |value: u8| value as f32 / max_value as f32 * 2.0 - 1.0;
+        let vectors = pixels.chunks(3).map(|rgb| Vector2::new(channel(rgb[0]), channel(rgb[1]))).collect();
+        Ok(Self::new(width, height, vectors))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Bilinearly interpolated vector at normalized coordinates, where (0, 0)
+    /// is the top-left node and (1, 1) the bottom-right one. Coordinates
+    /// outside that range are clamped to the edge.
+    pub fn sample(&self, u: f32, v: f32) -> Vector2<f32> {
+        let x = u.clamp(0.0, 1.0) * (self.width - 1) as f32;
+        let y = v.clamp(0.0, 1.0) * (self.height - 1) as f32;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+        let at = |x, y| self.vectors[y * self.width + x];
+        let top = at(x0, y0).lerp(&at(x1, y0), tx);
+        let bottom = at(x0, y1).lerp(&at(x1, y1), tx);
+        top.lerp(&bottom, ty)
+    }
+}
+
+// Number of nodes in a grid read from a file, whose header may claim any size
+fn grid_len(width: usize, height: usize) -> Result<usize, GridError> {
+    width.checked_mul(height)
+        .ok_or_else(|| GridError::Invalid(format!("grid size {}x{} is too large", width, height)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_text_grids() {
+        let grid = VectorGrid::from_text("# a comment\n2 2\n1 0  0 1\n-1 0  0 -1  # trailing\n").unwrap();
+        assert_eq!((grid.width(), grid.height()), (2, 2));
+        assert_eq!(grid.sample(0.0, 0.0), Vector2::new(1.0, 0.0));
+        assert_eq!(grid.sample(1.0, 1.0), Vector2::new(0.0, -1.0));
+        assert_eq!(grid.sample(0.5, 0.0), Vector2::new(0.5, 0.5));
+    }
+
+    #[test]
+    fn rejects_malformed_text_grids() {
+        for text in ["", "2", "0 1\n", "2 1\n1 0 0", "2 1\n1 0 0 x", "4294967296 4294967296\n1 0"] {
+            assert!(matches!(VectorGrid::from_text(text), Err(GridError::Invalid(_))), "{:?} should be rejected", text);
+        }
+    }
+
+    #[test]
+    fn parses_ppm_grids() {
+        let mut bytes = b"P6\n# flow\n2 1\n255\n".to_vec();
+        bytes.extend_from_slice(&[255, 0, 7, 0, 255, 7]);
+        let grid = VectorGrid::from_ppm(&bytes).unwrap();
+        assert_eq!((grid.width(), grid.height()), (2, 1));
+        assert_eq!(grid.sample(0.0, 0.0), Vector2::new(1.0, -1.0));
+        assert_eq!(grid.sample(1.0, 0.0), Vector2::new(-1.0, 1.0));
+    }
+
+    #[test]
+    fn rejects_malformed_ppm_grids() {
+        for bytes in [
+            &b"P3\n1 1\n255\n0 0 0"[..],
+            b"P6\n2 1\n255\n\x00\x00\x00",
+            b"P6\n1 1\n65535\n\x00\x00\x00\x00\x00\x00",
+            b"P6\n4294967296 4294967296\n255\n",
+            b"P6\n1",
+        ] {
+            assert!(matches!(VectorGrid::from_ppm(bytes), Err(GridError::Invalid(_))));
+        }
+    }
+}
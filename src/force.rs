@@ -1,8 +1,70 @@
+use crate::field::VectorGrid;
+use crate::noise::Noise;
+use crate::particle::State;
 use nalgebra::Vector2;
 
-// Force trait for implementing different types of forces
-pub trait Force {
-    fn apply(&self, position: &Vector2<f32>, velocity: &Vector2<f32>, mass: f32, mouse_pos: Option<&Vector2<f32>>) -> Vector2<f32>;
+/// Mouse state forwarded from the frontend.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InputState {
+    /// Cursor position in simulation coordinates, `None` without a cursor
+    pub mouse_position: Option<Vector2<f32>>,
+    pub left_button: bool,
+    pub right_button: bool,
+}
+
+/// Everything a force may depend on when evaluated for one particle.
+/// New inputs are added here rather than to the `Force::apply` signature.
+pub struct ForceContext<'a> {
+    /// Simulation time of the state being evaluated, including the offset of
+    /// intermediate integrator stages
+    pub time: f32,
+    /// Length of the step being taken
+    pub dt: f32,
+    /// Index of the particle in the simulation's particle store
+    pub index: usize,
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub mass: f32,
+    pub charge: f32,
+    pub radius: f32,
+    pub group: u8,
+    pub input: &'a InputState,
+    /// Simulation width and height
+    pub bounds: Vector2<f32>,
+    /// States of all particle slots at the same stage, indexed like `index`;
+    /// free slots are marked in `ParticleStore::alive`
+    pub states: &'a [State],
+}
+
+// Force trait for implementing different types of forces.
+// Forces are evaluated for many particles in parallel, so they must be thread-safe.
+pub trait Force: Send + Sync {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32>;
+
+    /// Sets a numeric parameter by name, returning `false` if there is no such
+    /// parameter. This is how animation curves drive forces.
+    fn set_parameter(&mut self, _name: &str, _value: f32) -> bool {
+        false
+    }
+
+    /// Called once at the start of every step with the current simulation time,
+    /// for forces that change over time.
+    fn advance(&mut self, _time: f32) {}
+}
+
+// Parameters of a direction vector: its components, or an angle in degrees
+// clockwise from the +x axis (y points down, so 90 is straight down)
+fn set_direction(direction: &mut Vector2<f32>, name: &str, value: f32) -> bool {
+    match name {
+        "direction_x" => direction.x = value,
+        "direction_y" => direction.y = value,
+        "angle" => {
+            let angle = value.to_radians();
+            *direction = Vector2::new(angle.cos(), angle.sin());
+        }
+        _ => return false,
+    }
+    true
 }
 
 // Gravity implementation
@@ -21,8 +83,16 @@ impl Default for Gravity {
 }
 
 impl Force for Gravity {
-    fn apply(&self, _position: &Vector2<f32>, _velocity: &Vector2<f32>, mass: f32, _mouse_pos: Option<&Vector2<f32>>) -> Vector2<f32> {
-        self.direction * self.strength * mass
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        self.direction * self.strength * ctx.mass
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "strength" => self.strength = value,
+            _ => return set_direction(&mut self.direction, name, value),
+        }
+        true
     }
 }
 
@@ -33,9 +103,272 @@ pub struct Wind {
 }
 
 impl Force for Wind {
-    fn apply(&self, _position: &Vector2<f32>, _velocity: &Vector2<f32>, _mass: f32, _mouse_pos: Option<&Vector2<f32>>) -> Vector2<f32> {
+    fn apply(&self, _ctx: &ForceContext) -> Vector2<f32> {
         self.direction * self.strength
     }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "strength" => self.strength = value,
+            _ => return set_direction(&mut self.direction, name, value),
+        }
+        true
+    }
+}
+
+// Linear (Stokes) drag, F = -b v: the regime of slow or small bodies in a
+// viscous medium. Without other forces a particle's speed decays by a factor
+// e^(-b/m) every second, regardless of the timestep.
+pub struct LinearDrag {
+    pub coefficient: f32,
+}
+
+impl Force for LinearDrag {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        -self.coefficient * ctx.velocity
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "coefficient" => self.coefficient = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Quadratic (aerodynamic) drag, F = -c |v| v, which dominates at high speed.
+// `coefficient` folds together ½ ρ C_d A.
+pub struct QuadraticDrag {
+    pub coefficient: f32,
+}
+
+impl Force for QuadraticDrag {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        -self.coefficient * ctx.velocity.norm() * ctx.velocity
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "coefficient" => self.coefficient = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Swirling turbulence from curl noise. The noise is a scalar potential ψ and
+// the force follows its curl (∂ψ/∂y, -∂ψ/∂x), which is divergence-free: it
+// stirs particles around without gathering them into sinks or sources.
+pub struct Turbulence {
+    pub noise: Noise,
+    pub scale: f32,    // Size of the swirls in simulation units
+    pub strength: f32, // Typical acceleration
+    pub octaves: u32,  // Layers of finer detail
+    pub speed: f32,    // How fast the field evolves, in noise units per second
+}
+
+impl Turbulence {
+    pub fn new(scale: f32, strength: f32) -> Self {
+        Self {
+            noise: Noise::new(0),
+            scale,
+            strength,
+            octaves: 3,
+            speed: 0.5,
+        }
+    }
+}
+
+impl Force for Turbulence {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        // Central differences in noise space, so the magnitude doesn't depend on `scale`
+        const EPSILON: f32 = 1e-3;
+        let x = ctx.position.x / self.scale;
+        let y = ctx.position.y / self.scale;
+        let z = ctx.time * self.speed;
+        let potential = |x, y| self.noise.fractal(x, y, z, self.octaves);
+
+        let dx = potential(x + EPSILON, y) - potential(x - EPSILON, y);
+        let dy = potential(x, y + EPSILON) - potential(x, y - EPSILON);
+        Vector2::new(dy, -dx) / (2.0 * EPSILON) * self.strength * ctx.mass
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "scale" => self.scale = value,
+            "strength" => self.strength = value,
+            "speed" => self.speed = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// How the strength of a placed force changes with distance from its center.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    /// Full strength everywhere within the radius
+    Constant,
+    /// Fades linearly to zero at the radius
+    Linear,
+    /// 1/r
+    Inverse,
+    /// 1/r², like gravity
+    InverseSquare,
+    /// Fades smoothly to zero at the radius, with no kink at either end
+    Smoothstep,
+}
+
+impl Falloff {
+    /// Strength multiplier at `distance` from the center. `softening` keeps the
+    /// inverse laws finite near the center (r² becomes r² + softening²), and
+    /// nothing beyond `max_radius` is affected. `Linear` and `Smoothstep` fade
+    /// over `max_radius`, so they need it to be finite.
+    pub fn weight(self, distance: f32, softening: f32, max_radius: f32) -> f32 {
+        if distance > max_radius {
+            return 0.0;
+        }
+        let softened_squared = distance * distance + softening * softening;
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - distance / max_radius,
+            Falloff::Inverse => 1.0 / softened_squared.sqrt(),
+            Falloff::InverseSquare => 1.0 / softened_squared,
+            Falloff::Smoothstep => {
+                let t = distance / max_radius;
+                1.0 - t * t * (3.0 - 2.0 * t)
+            }
+        }
+    }
+}
+
+// World-anchored point attractor. Pulls particles toward `position`; a negative
+// strength pushes them away instead, making it a repulsor.
+pub struct Attractor {
+    pub position: Vector2<f32>,
+    pub strength: f32,
+    pub falloff: Falloff,
+    pub softening: f32,
+    pub max_radius: f32, // f32::INFINITY for unlimited reach
+}
+
+impl Force for Attractor {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        let diff = self.position - ctx.position;
+        let distance = diff.norm();
+        if distance < f32::EPSILON {
+            return Vector2::new(0.0, 0.0);
+        }
+        let weight = self.falloff.weight(distance, self.softening, self.max_radius);
+        diff / distance * self.strength * weight * ctx.mass
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "x" => self.position.x = value,
+            "y" => self.position.y = value,
+            "strength" => self.strength = value,
+            "softening" => self.softening = value,
+            "max_radius" => self.max_radius = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// World-anchored point repulsor: an attractor pushing particles away with its
+// strength instead, so animating `strength` keeps it repulsive.
+pub struct Repulsor(pub Attractor);
+
+impl Force for Repulsor {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        -self.0.apply(ctx)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        self.0.set_parameter(name, value)
+    }
+}
+
+// World-anchored vortex. Pushes particles tangentially around `position`,
+// clockwise on screen for positive strength (y points down).
+pub struct Vortex {
+    pub position: Vector2<f32>,
+    pub strength: f32,
+    pub falloff: Falloff,
+    pub softening: f32,
+    pub max_radius: f32, // f32::INFINITY for unlimited reach
+}
+
+impl Force for Vortex {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        let diff = ctx.position - self.position;
+        let distance = diff.norm();
+        if distance < f32::EPSILON {
+            return Vector2::new(0.0, 0.0);
+        }
+        let weight = self.falloff.weight(distance, self.softening, self.max_radius);
+        Vector2::new(-diff.y, diff.x) / distance * self.strength * weight * ctx.mass
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "x" => self.position.x = value,
+            "y" => self.position.y = value,
+            "strength" => self.strength = value,
+            "softening" => self.softening = value,
+            "max_radius" => self.max_radius = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Authored flow field. The grid is stretched over the simulation bounds and
+// interpolated between its nodes; each vector is an acceleration in units of
+// `strength`.
+pub struct VectorField {
+    pub grid: VectorGrid,
+    pub strength: f32,
+}
+
+impl Force for VectorField {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        let u = ctx.position.x / ctx.bounds.x;
+        let v = ctx.position.y / ctx.bounds.y;
+        self.grid.sample(u, v) * self.strength * ctx.mass
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "strength" => self.strength = value,
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Uniform magnetic field perpendicular to the simulation plane. Applies the
+// Lorentz force q v × B, which bends charged particles into circles without
+// changing their speed. Positive strength points into the screen (along x × y,
+// with y pointing down), which turns positive charges counterclockwise on screen.
+pub struct MagneticField {
+    pub strength: f32,
+}
+
+impl Force for MagneticField {
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        Vector2::new(ctx.velocity.y, -ctx.velocity.x) * ctx.charge * self.strength
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "strength" => self.strength = value,
+            _ => return false,
+        }
+        true
+    }
 }
 
 // Mouse-based gravity implementation
@@ -52,9 +385,9 @@ impl Default for MouseGravity {
 }
 
 impl Force for MouseGravity {
-    fn apply(&self, position: &Vector2<f32>, _velocity: &Vector2<f32>, mass: f32, mouse_pos: Option<&Vector2<f32>>) -> Vector2<f32> {
-        if let Some(mouse) = mouse_pos {
-            let diff = mouse - position;
+    fn apply(&self, ctx: &ForceContext) -> Vector2<f32> {
+        if let Some(mouse) = ctx.input.mouse_position {
+            let diff = mouse - ctx.position;
             let distance_squared = diff.norm_squared();
             
             // Avoid divide by zero and extreme forces when too close
@@ -63,7 +396,7 @@ impl Force for MouseGravity {
             }
             
             // Inverse square law (like real gravity)
-            let force_magnitude = self.strength * mass / (distance_squared.sqrt());
+            let force_magnitude = self.strength * ctx.mass / (distance_squared.sqrt());
             let direction = diff.normalize();
             
             direction * force_magnitude
@@ -71,4 +404,12 @@ impl Force for MouseGravity {
             Vector2::new(0.0, 0.0) // No force if mouse position not available
         }
     }
-} 
\ No newline at end of file
+
+    fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        match name {
+            "strength" => self.strength = value,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,88 @@
+//! Particle groups.
+//!
+//! Every particle belongs to one of [`MAX_GROUPS`] groups (layers). Forces can
+//! be limited to some groups with a [`GroupMask`], and a [`GroupMatrix`] says
+//! between which groups collisions and pair forces act.
+
+/// Number of distinct groups a particle can belong to.
+pub const MAX_GROUPS: usize = 32;
+
+/// A set of groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GroupMask(u32);
+
+impl GroupMask {
+    pub const ALL: GroupMask = GroupMask(u32::MAX);
+    pub const NONE: GroupMask = GroupMask(0);
+
+    /// Mask containing only `group`.
+    pub fn only(group: u8) -> Self {
+        Self::NONE.with(group)
+    }
+
+    /// This mask with `group` added. Groups past [`MAX_GROUPS`] are ignored.
+    pub fn with(self, group: u8) -> Self {
+        GroupMask(self.0 | 1u32.checked_shl(group as u32).unwrap_or(0))
+    }
+
+    pub fn contains(self, group: u8) -> bool {
+        self.0 & 1u32.checked_shl(group as u32).unwrap_or(0) != 0
+    }
+}
+
+impl Default for GroupMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl FromIterator<u8> for GroupMask {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::NONE, GroupMask::with)
+    }
+}
+
+/// An N×N matrix of which groups act on which. Row `target` holds the groups
+/// whose particles act on particles of group `target`; for collisions and
+/// other symmetric interactions the matrix should be symmetric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GroupMatrix {
+    rows: [GroupMask; MAX_GROUPS],
+}
+
+impl GroupMatrix {
+    /// Every group acts on every group.
+    pub const ALL: GroupMatrix = GroupMatrix { rows: [GroupMask::ALL; MAX_GROUPS] };
+    /// No group acts on any group.
+    pub const NONE: GroupMatrix = GroupMatrix { rows: [GroupMask::NONE; MAX_GROUPS] };
+
+    /// Whether particles of group `source` act on particles of group `target`.
+    pub fn get(&self, target: u8, source: u8) -> bool {
+        self.row(target).contains(source)
+    }
+
+    /// Groups acting on particles of group `target`.
+    pub fn row(&self, target: u8) -> GroupMask {
+        self.rows.get(target as usize).copied().unwrap_or(GroupMask::NONE)
+    }
+
+    /// Sets whether `source` acts on `target`.
+    pub fn set(&mut self, target: u8, source: u8, enabled: bool) {
+        if let Some(row) = self.rows.get_mut(target as usize) {
+            let bit = GroupMask::only(source).0;
+            row.0 = if enabled { row.0 | bit } else { row.0 & !bit };
+        }
+    }
+
+    /// Sets both directions between `a` and `b`.
+    pub fn set_symmetric(&mut self, a: u8, b: u8, enabled: bool) {
+        self.set(a, b, enabled);
+        self.set(b, a, enabled);
+    }
+}
+
+impl Default for GroupMatrix {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
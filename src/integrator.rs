@@ -0,0 +1,105 @@
+use crate::particle::{State, StateDerivative};
+
+/// Numerical scheme used to advance particle states over one timestep.
+///
+/// `evaluate(offset, states)` returns the derivative of every particle at the
+/// states it is given, `offset` seconds into the step, re-sampling all forces,
+/// so schemes are free to evaluate as many intermediate stages as they need.
+pub trait Integrator: Send + Sync {
+    fn integrate(&self, states: &mut [State], dt: f32, evaluate: &Evaluate);
+}
+
+/// Derivative evaluation callback passed to [`Integrator::integrate`].
+pub type Evaluate<'a> = dyn Fn(f32, &[State]) -> Vec<StateDerivative> + 'a;
+
+/// First-order explicit (forward) Euler. One force evaluation per step; cheapest,
+/// but gains energy over time.
+pub struct ExplicitEuler;
+
+impl Integrator for ExplicitEuler {
+    fn integrate(&self, states: &mut [State], dt: f32, evaluate: &Evaluate) {
+        let derivatives = evaluate(0.0, states);
+        for (state, derivative) in states.iter_mut().zip(&derivatives) {
+            *state = state.advanced(derivative, dt);
+        }
+    }
+}
+
+/// Semi-implicit (symplectic) Euler: updates velocity first, then moves with
+/// the new velocity. Same cost as explicit Euler but bounded energy error.
+pub struct SemiImplicitEuler;
+
+impl Integrator for SemiImplicitEuler {
+    fn integrate(&self, states: &mut [State], dt: f32, evaluate: &Evaluate) {
+        let derivatives = evaluate(0.0, states);
+        for (state, derivative) in states.iter_mut().zip(&derivatives) {
+            state.velocity += derivative.dv * dt;
+            state.position += state.velocity * dt;
+        }
+    }
+}
+
+/// Velocity Verlet: second-order and symplectic, two force evaluations per step.
+/// Forces at the end of the step are sampled with a predicted velocity so
+/// velocity-dependent forces still see a sensible state.
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn integrate(&self, states: &mut [State], dt: f32, evaluate: &Evaluate) {
+        let start = evaluate(0.0, states);
+        let predicted: Vec<State> = states.iter().zip(&start).map(|(state, derivative)| State {
+            position: state.position + state.velocity * dt + derivative.dv * (0.5 * dt * dt),
+            velocity: state.velocity + derivative.dv * dt,
+        }).collect();
+        let end = evaluate(dt, &predicted);
+
+        for (i, state) in states.iter_mut().enumerate() {
+            state.position = predicted[i].position;
+            state.velocity += (start[i].dv + end[i].dv) * (0.5 * dt);
+        }
+    }
+}
+
+/// Leapfrog in drift-kick-drift form: second-order and symplectic with a
+/// single force evaluation per step, taken at the half-step position.
+pub struct Leapfrog;
+
+impl Integrator for Leapfrog {
+    fn integrate(&self, states: &mut [State], dt: f32, evaluate: &Evaluate) {
+        for state in states.iter_mut() {
+            state.position += state.velocity * (0.5 * dt);
+        }
+        let derivatives = evaluate(0.5 * dt, states);
+        for (state, derivative) in states.iter_mut().zip(&derivatives) {
+            state.velocity += derivative.dv * dt;
+            state.position += state.velocity * (0.5 * dt);
+        }
+    }
+}
+
+/// Classic fourth-order Runge-Kutta with four force evaluations per step.
+/// Most accurate for smooth forces, but not symplectic.
+pub struct Rk4;
+
+impl Integrator for Rk4 {
+    fn integrate(&self, states: &mut [State], dt: f32, evaluate: &Evaluate) {
+        let k1 = evaluate(0.0, states);
+        let k2 = evaluate(dt * 0.5, &advance(states, &k1, dt * 0.5));
+        let k3 = evaluate(dt * 0.5, &advance(states, &k2, dt * 0.5));
+        let k4 = evaluate(dt, &advance(states, &k3, dt));
+
+        // Combine derivatives with proper weights
+        for (i, state) in states.iter_mut().enumerate() {
+            let derivative = StateDerivative {
+                dp: (k1[i].dp + (k2[i].dp + k3[i].dp) * 2.0 + k4[i].dp) * (1.0 / 6.0),
+                dv: (k1[i].dv + (k2[i].dv + k3[i].dv) * 2.0 + k4[i].dv) * (1.0 / 6.0),
+            };
+            *state = state.advanced(&derivative, dt);
+        }
+    }
+}
+
+// Advance every state along its derivative by `dt`
+fn advance(states: &[State], derivatives: &[StateDerivative], dt: f32) -> Vec<State> {
+    states.iter().zip(derivatives).map(|(state, derivative)| state.advanced(derivative, dt)).collect()
+}
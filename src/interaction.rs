@@ -1,35 +1,441 @@
-use crate::particle::Particle;
+use crate::barnes_hut::{Aggregate, BarnesHutTree};
+use crate::broadphase::{Aabb, BroadPhase, UniformGrid};
+use crate::group::{GroupMatrix, MAX_GROUPS};
+use crate::particle::limit_force;
 use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rayon::prelude::*;
 
 const EQUILIBRIUM_DISTANCE: f32 = 50.0;
 const INTERACTION_COEFFICIENT: f32 = 0.05;
 
-/// Applies a spring-like attraction/repulsion force between each unique pair of particles.
-/// Particles separated by more than EQUILIBRIUM_DISTANCE experience an attractive force,
+// Pairs closer than this are skipped to prevent division by zero
+const MIN_DISTANCE: f32 = 0.0001;
+
+/// How inter-particle forces without a cutoff are summed. Forces with a
+/// cutoff radius only ever visit neighbours within it.
+#[derive(Clone, Copy, Debug)]
+pub enum PairSolver {
+    /// Exact sum over every pair, O(n²).
+    Direct,
+    /// Barnes-Hut approximation of far-field contributions, O(n log n).
+    /// Smaller opening angles `theta` are more accurate and slower.
+    BarnesHut { theta: f32 },
+}
+
+/// A particle taking part in a pair interaction.
+#[derive(Clone, Copy, Debug)]
+pub struct PairBody {
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+    pub mass: f32,
+    pub charge: f32,
+    pub radius: f32,
+    pub group: u8,
+}
+
+/// Summary of a distant group of particles, used by the Barnes-Hut solver.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cluster {
+    pub count: f32,
+    pub mass: f32,
+    pub charge: f32,
+    pub radius: f32, // Sum of the radii
+    pub group: u8,   // Clusters never mix groups
+}
+
+impl Aggregate for Cluster {
+    fn add(&mut self, other: &Self) {
+        self.count += other.count;
+        self.mass += other.mass;
+        self.charge += other.charge;
+        self.radius += other.radius;
+        self.group = other.group;
+    }
+
+    fn weight(&self) -> f32 {
+        self.mass
+    }
+}
+
+/// Force between two particles, registered on a `Simulation` alongside the
+/// single-body `Force`s.
+pub trait PairForce: Send + Sync {
+    /// Force on `a` exerted by `b`, where `offset` is `b.position - a.position`.
+    /// It need not be symmetric: the force on `b` is evaluated separately.
+    fn force(&self, offset: Vector2<f32>, a: &PairBody, b: &PairBody) -> Vector2<f32>;
+
+    /// Distance beyond which the force vanishes, or `None` if it never cuts off.
+    /// Forces with a cutoff only visit neighbours within it.
+    fn cutoff(&self) -> Option<f32> {
+        None
+    }
+
+    /// Force on `a` from a distant cluster of particles at `offset`, used by
+    /// the Barnes-Hut solver. Defaults to `count` copies of the cluster's
+    /// average particle, which is right for forces linear in the source's
+    /// attributes.
+    fn cluster_force(&self, offset: Vector2<f32>, a: &PairBody, cluster: &Cluster) -> Vector2<f32> {
+        let average = PairBody {
+            position: a.position + offset,
+            velocity: Vector2::new(0.0, 0.0),
+            mass: cluster.mass / cluster.count,
+            charge: cluster.charge / cluster.count,
+            radius: cluster.radius / cluster.count,
+            group: cluster.group,
+        };
+        self.force(offset, a, &average) * cluster.count
+    }
+
+    /// Re-draws any randomly generated parameters, e.g. interaction rules.
+    /// Forces without random parameters ignore it.
+    fn randomize(&mut self, _rng: &mut StdRng) {}
+}
+
+/// Spring-like attraction/repulsion between every pair of particles.
+/// Particles separated by more than `equilibrium_distance` experience an attractive force,
 /// while those closer than that distance are repelled.
-pub fn apply_attraction_repulsion(particles: &mut [Particle]) {
-    let len = particles.len();
-    for i in 0..len {
-        for j in (i+1)..len {
-            let (p_i, p_j) = {
-                let (left, right) = particles.split_at_mut(j);
-                (&mut left[i], &mut right[0])
-            };
-            
-            let diff = p_j.position - p_i.position;
-            let distance = diff.norm();
-            if distance < 0.0001 {
-                continue; // Prevent division by zero
+#[derive(Clone, Copy, Debug)]
+pub struct LinearSpring {
+    pub equilibrium_distance: f32,
+    pub coefficient: f32,
+    pub cutoff: Option<f32>,
+}
+
+impl Default for LinearSpring {
+    fn default() -> Self {
+        Self {
+            equilibrium_distance: EQUILIBRIUM_DISTANCE,
+            coefficient: INTERACTION_COEFFICIENT,
+            cutoff: None,
+        }
+    }
+}
+
+impl PairForce for LinearSpring {
+    fn force(&self, offset: Vector2<f32>, _a: &PairBody, _b: &PairBody) -> Vector2<f32> {
+        let distance = offset.norm();
+        // Linear (spring-like) force based on deviation from the equilibrium distance:
+        let force_magnitude = self.coefficient * (distance - self.equilibrium_distance);
+        force_magnitude * offset / distance
+    }
+
+    fn cutoff(&self) -> Option<f32> {
+        self.cutoff
+    }
+}
+
+/// Lennard-Jones 12-6 potential: strong short-range repulsion with a shallow
+/// attractive well of depth `epsilon` at a distance of 2^(1/6) `sigma`.
+#[derive(Clone, Copy, Debug)]
+pub struct LennardJones {
+    pub epsilon: f32,
+    pub sigma: f32,
+    pub cutoff: f32,
+}
+
+impl LennardJones {
+    /// Uses the conventional cutoff of 2.5 `sigma`.
+    pub fn new(epsilon: f32, sigma: f32) -> Self {
+        Self {
+            epsilon,
+            sigma,
+            cutoff: 2.5 * sigma,
+        }
+    }
+}
+
+impl PairForce for LennardJones {
+    fn force(&self, offset: Vector2<f32>, _a: &PairBody, _b: &PairBody) -> Vector2<f32> {
+        let distance = offset.norm();
+        let s6 = (self.sigma / distance).powi(6);
+        // dV/dr of V(r) = 4ε((σ/r)¹² - (σ/r)⁶); positive pulls the particles together
+        let slope = 24.0 * self.epsilon / distance * (s6 - 2.0 * s6 * s6);
+        slope * offset / distance
+    }
+
+    fn cutoff(&self) -> Option<f32> {
+        Some(self.cutoff)
+    }
+}
+
+/// Morse potential: a well of depth `depth` at `equilibrium_distance`, with
+/// `width` controlling how quickly it widens. Softer than Lennard-Jones at short range.
+#[derive(Clone, Copy, Debug)]
+pub struct Morse {
+    pub depth: f32,
+    pub width: f32,
+    pub equilibrium_distance: f32,
+    pub cutoff: f32,
+}
+
+impl PairForce for Morse {
+    fn force(&self, offset: Vector2<f32>, _a: &PairBody, _b: &PairBody) -> Vector2<f32> {
+        let distance = offset.norm();
+        let decay = (-self.width * (distance - self.equilibrium_distance)).exp();
+        // dV/dr of V(r) = D(1 - e^(-a(r - r0)))²
+        let slope = 2.0 * self.depth * self.width * decay * (1.0 - decay);
+        slope * offset / distance
+    }
+
+    fn cutoff(&self) -> Option<f32> {
+        Some(self.cutoff)
+    }
+}
+
+/// Soft-core repulsion that falls off linearly to zero at `radius`. Stays
+/// finite when particles overlap, so it is stable at large timesteps.
+#[derive(Clone, Copy, Debug)]
+pub struct SoftRepulsion {
+    pub strength: f32,
+    pub radius: f32,
+}
+
+impl PairForce for SoftRepulsion {
+    fn force(&self, offset: Vector2<f32>, _a: &PairBody, _b: &PairBody) -> Vector2<f32> {
+        let distance = offset.norm();
+        if distance >= self.radius {
+            return Vector2::new(0.0, 0.0);
+        }
+        -self.strength * (1.0 - distance / self.radius) * offset / distance
+    }
+
+    fn cutoff(&self) -> Option<f32> {
+        Some(self.radius)
+    }
+}
+
+/// Electrostatic (Coulomb) force between charged particles: like charges repel,
+/// opposite charges attract, falling off with the inverse square of distance.
+/// `softening` keeps the force finite when particles get very close.
+#[derive(Clone, Copy, Debug)]
+pub struct Coulomb {
+    pub strength: f32,
+    pub softening: f32,
+}
+
+impl PairForce for Coulomb {
+    fn force(&self, offset: Vector2<f32>, a: &PairBody, b: &PairBody) -> Vector2<f32> {
+        let softened = offset.norm_squared() + self.softening * self.softening;
+        -self.strength * a.charge * b.charge * offset / (softened * softened.sqrt())
+    }
+}
+
+/// "Particle Life" rules between species, where each particle's group is its
+/// species. Within `repulsion * radius` particles always push each other apart;
+/// beyond that, up to `radius`, a particle of species `a` is pulled toward (or,
+/// for negative values, pushed away from) one of species `b` according to
+/// `attraction(a, b)`. The rules need not be symmetric, so chasing and
+/// orbiting patterns emerge.
+#[derive(Clone, Debug)]
+pub struct ParticleLife {
+    species: usize,
+    attraction: Vec<f32>, // species × species, row-major by the attracted species
+    pub radius: f32,
+    pub repulsion: f32, // Fraction of `radius`
+    pub strength: f32,
+}
+
+impl ParticleLife {
+    /// Rules for `species` species, all neutral.
+    pub fn new(species: usize, radius: f32, strength: f32) -> Self {
+        Self {
+            species,
+            attraction: vec![0.0; species * species],
+            radius,
+            repulsion: 0.3,
+            strength,
+        }
+    }
+
+    pub fn species(&self) -> usize {
+        self.species
+    }
+
+    /// How strongly species `a` is drawn to species `b`, in [-1, 1].
+    pub fn attraction(&self, a: usize, b: usize) -> f32 {
+        self.attraction[a * self.species + b]
+    }
+
+    pub fn set_attraction(&mut self, a: usize, b: usize, value: f32) {
+        self.attraction[a * self.species + b] = value;
+    }
+}
+
+impl PairForce for ParticleLife {
+    fn force(&self, offset: Vector2<f32>, a: &PairBody, b: &PairBody) -> Vector2<f32> {
+        let (species_a, species_b) = (a.group as usize, b.group as usize);
+        if species_a >= self.species || species_b >= self.species {
+            return Vector2::new(0.0, 0.0);
+        }
+        let distance = offset.norm();
+        let r = distance / self.radius;
+        let magnitude = if r < self.repulsion {
+            // Linear repulsion, strongest when overlapping
+            r / self.repulsion - 1.0
+        } else if r < 1.0 {
+            // Triangular attraction profile peaking halfway through the band
+            let peak = 1.0 - (2.0 * r - 1.0 - self.repulsion).abs() / (1.0 - self.repulsion);
+            self.attraction(species_a, species_b) * peak
+        } else {
+            0.0
+        };
+        self.strength * magnitude * offset / distance
+    }
+
+    fn cutoff(&self) -> Option<f32> {
+        Some(self.radius)
+    }
+
+    fn randomize(&mut self, rng: &mut StdRng) {
+        for value in &mut self.attraction {
+            *value = rng.gen_range(-1.0..=1.0);
+        }
+    }
+}
+
+/// Accumulates every pair force between the `bodies` into `forces`. Each
+/// force only acts on a body from the groups in the body's row of its matrix.
+///
+/// Particles are processed in parallel, each one summing the forces from the
+/// others into its own entry. Every pair is evaluated from both sides, but no two
+/// threads ever write to the same entry and the summation order is fixed, so
+/// results are race-free and deterministic.
+pub fn apply_pair_forces(
+    pair_forces: &[(Box<dyn PairForce>, GroupMatrix)],
+    bodies: &[PairBody],
+    solver: PairSolver,
+    forces: &mut [Vector2<f32>],
+) {
+    for (pair_force, groups) in pair_forces {
+        let pair_force = pair_force.as_ref();
+        match (pair_force.cutoff(), solver) {
+            (Some(cutoff), _) => apply_within_cutoff(pair_force, groups, cutoff, bodies, forces),
+            (None, PairSolver::Direct) => apply_direct(pair_force, groups, bodies, forces),
+            (None, PairSolver::BarnesHut { theta }) => apply_barnes_hut(pair_force, groups, theta, bodies, forces),
+        }
+    }
+}
+
+// Force on body `i` from body `j`, or nothing if they coincide
+fn pair_force_on(pair_force: &dyn PairForce, bodies: &[PairBody], i: usize, j: usize) -> Vector2<f32> {
+    let offset = bodies[j].position - bodies[i].position;
+    if offset.norm() < MIN_DISTANCE {
+        return Vector2::new(0.0, 0.0);
+    }
+    limit_force(pair_force.force(offset, &bodies[i], &bodies[j]))
+}
+
+// Exact sum over every pair
+fn apply_direct(pair_force: &dyn PairForce, groups: &GroupMatrix, bodies: &[PairBody], forces: &mut [Vector2<f32>]) {
+    forces.par_iter_mut().enumerate().for_each(|(i, force)| {
+        let sources = groups.row(bodies[i].group);
+        for j in 0..bodies.len() {
+            if j != i && sources.contains(bodies[j].group) {
+                *force += pair_force_on(pair_force, bodies, i, j);
             }
-            
-            let direction = diff / distance;
-            // Linear (spring-like) force based on deviation from EQUILIBRIUM_DISTANCE:
-            let force_magnitude = INTERACTION_COEFFICIENT * (distance - EQUILIBRIUM_DISTANCE);
-            let force = force_magnitude * direction;
-            
-            // Apply equal and opposite forces to each particle:
-            p_i.apply_force(force);
-            p_j.apply_force(-force);
-        }
-    }
-} 
\ No newline at end of file
+        }
+    });
+}
+
+// Sum over neighbours within `cutoff`, found with a uniform grid
+fn apply_within_cutoff(
+    pair_force: &dyn PairForce,
+    groups: &GroupMatrix,
+    cutoff: f32,
+    bodies: &[PairBody],
+    forces: &mut [Vector2<f32>],
+) {
+    // Boxes of half the cutoff overlap exactly when both coordinates are within it
+    let boxes: Vec<Aabb> = bodies.iter().map(|body| Aabb::around(body.position, cutoff * 0.5)).collect();
+    let mut pairs = Vec::new();
+    UniformGrid::new(cutoff).find_pairs(&boxes, &mut pairs);
+
+    // Neighbour lists in compressed form: neighbours of i are in
+    // neighbours[starts[i]..starts[i + 1]], in the order the grid reported them
+    let mut starts = vec![0; bodies.len() + 1];
+    for &(i, j) in &pairs {
+        starts[i + 1] += 1;
+        starts[j + 1] += 1;
+    }
+    for i in 0..bodies.len() {
+        starts[i + 1] += starts[i];
+    }
+    let mut fill = starts.clone();
+    let mut neighbours = vec![0; starts[bodies.len()]];
+    for &(i, j) in &pairs {
+        neighbours[fill[i]] = j;
+        fill[i] += 1;
+        neighbours[fill[j]] = i;
+        fill[j] += 1;
+    }
+
+    let cutoff_squared = cutoff * cutoff;
+    forces.par_iter_mut().enumerate().for_each(|(i, force)| {
+        let sources = groups.row(bodies[i].group);
+        for &j in &neighbours[starts[i]..starts[i + 1]] {
+            let within = (bodies[j].position - bodies[i].position).norm_squared() <= cutoff_squared;
+            if within && sources.contains(bodies[j].group) {
+                *force += pair_force_on(pair_force, bodies, i, j);
+            }
+        }
+    });
+}
+
+// Barnes-Hut approximation: distant groups of particles act as a single
+// cluster at their center of mass
+fn apply_barnes_hut(
+    pair_force: &dyn PairForce,
+    groups: &GroupMatrix,
+    theta: f32,
+    bodies: &[PairBody],
+    forces: &mut [Vector2<f32>],
+) {
+    // One tree per particle group, so every cluster holds a single group and
+    // forces that depend on the groups involved stay exact
+    let mut members: Vec<Vec<usize>> = vec![Vec::new(); MAX_GROUPS];
+    let mut slots = vec![0; bodies.len()]; // Position of each body in its group's members
+    for (j, body) in bodies.iter().enumerate() {
+        if let Some(group_members) = members.get_mut(body.group as usize) {
+            slots[j] = group_members.len();
+            group_members.push(j);
+        }
+    }
+    let trees: Vec<Option<BarnesHutTree<Cluster>>> = members.iter().map(|group_members| {
+        if group_members.is_empty() {
+            return None;
+        }
+        let positions: Vec<Vector2<f32>> = group_members.iter().map(|&j| bodies[j].position).collect();
+        let clusters: Vec<Cluster> = group_members.iter().map(|&j| Cluster {
+            count: 1.0,
+            mass: bodies[j].mass,
+            charge: bodies[j].charge,
+            radius: bodies[j].radius,
+            group: bodies[j].group,
+        }).collect();
+        Some(BarnesHutTree::new(&positions, &clusters))
+    }).collect();
+
+    forces.par_iter_mut().enumerate().for_each(|(i, force)| {
+        let sources = groups.row(bodies[i].group);
+        for (group, tree) in trees.iter().enumerate() {
+            let Some(tree) = tree.as_ref().filter(|_| sources.contains(group as u8)) else {
+                continue;
+            };
+            // Skip the body itself in its own group's tree
+            let index = if bodies[i].group as usize == group { slots[i] } else { usize::MAX };
+            let group_members = &members[group];
+            *force += tree.accumulate(
+                index,
+                bodies[i].position,
+                theta,
+                &|slot| pair_force_on(pair_force, bodies, i, group_members[slot]),
+                // Limit the cluster's average contribution, as if each pair was evaluated on its own
+                &|offset, cluster| {
+                    limit_force(pair_force.cluster_force(offset, &bodies[i], cluster) / cluster.count) * cluster.count
+                },
+            );
+        }
+    });
+}
@@ -0,0 +1,31 @@
+//! Real-time 2D particle simulation.
+//!
+//! The simulation itself has no dependency on a window or event loop, so it can
+//! be driven headlessly from tests and other tools. The `particle_system` binary
+//! is a thin minifb frontend on top of this library.
+
+pub mod animation;
+pub mod barnes_hut;
+pub mod boundary;
+pub mod broadphase;
+pub mod emitter;
+pub mod field;
+pub mod force;
+pub mod group;
+pub mod integrator;
+pub mod interaction;
+pub mod modifier;
+pub mod noise;
+pub mod particle;
+pub mod renderer;
+pub mod scene;
+pub mod simulation;
+pub mod store;
+
+pub use force::{Force, ForceContext};
+pub use integrator::Integrator;
+pub use particle::Particle;
+pub use renderer::Renderer;
+pub use scene::Scene;
+pub use simulation::Simulation;
+pub use store::ParticleStore;
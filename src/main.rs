@@ -1,60 +1,178 @@
-mod particle;
-mod simulation;
-mod renderer;
-mod force;
-mod interaction;
-
-use minifb::{Key, MouseMode, Window, WindowOptions};
-use simulation::Simulation;
-use renderer::Renderer;
+mod cli;
+
+use cli::{Command, Options};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 use nalgebra::Vector2;
+use particle_system::force::InputState;
+use particle_system::renderer::write_ppm;
+use particle_system::scene::DEFAULT_SCENE;
+use particle_system::{Renderer, Scene, Simulation};
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
 
 // Constants
-const WIDTH: usize = 800;
-const HEIGHT: usize = 600;
 const FPS_CAP: u64 = 60;
+// Fixed simulation timestep, independent of the display rate
+const FIXED_DT: f32 = 1.0 / 120.0;
+// Longest frame time fed into the accumulator, so a stall doesn't snowball
+const MAX_FRAME_TIME: f32 = 0.25;
 
 fn main() {
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\nFor more information, try `--help`.", e);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let simulation = build_simulation(options)?;
+    if let Some(output) = &options.output {
+        std::fs::create_dir_all(output)
+            .map_err(|e| format!("could not create output directory {}: {}", output.display(), e))?;
+    }
+
+    if options.headless {
+        run_headless(simulation, options)
+    } else {
+        run_windowed(simulation, options)
+    }
+}
+
+// Load the requested scene and apply the command-line overrides
+fn build_simulation(options: &Options) -> Result<Simulation, Box<dyn Error>> {
+    let mut scene = match &options.scene {
+        Some(path) => Scene::load(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => DEFAULT_SCENE.parse()?,
+    };
+
+    if let Some(width) = options.width {
+        scene.bounds.width = width;
+    }
+    if let Some(height) = options.height {
+        scene.bounds.height = height;
+    }
+    if let Some(particles) = options.particles {
+        for population in &mut scene.populations {
+            population.count = particles;
+        }
+    }
+    if options.seed.is_some() {
+        scene.seed = options.seed;
+    }
+
+    Ok(scene.build()?)
+}
+
+fn run_headless(mut simulation: Simulation, options: &Options) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (simulation.width(), simulation.height());
+    let mut renderer = Renderer::new(width, height);
+    let frame_time = 1.0 / FPS_CAP as f32;
+    let mut accumulator = 0.0;
+
+    // There is no cursor without a window
+    simulation.set_mouse_position(None);
+
+    for frame in 0..options.frames.unwrap_or(0) {
+        accumulator += frame_time;
+        while accumulator >= FIXED_DT {
+            simulation.step(FIXED_DT);
+            accumulator -= FIXED_DT;
+        }
+
+        if let Some(output) = &options.output {
+            save_frame(output, frame, &renderer.render(&simulation), width, height)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn run_windowed(mut simulation: Simulation, options: &Options) -> Result<(), Box<dyn Error>> {
+    let (width, height) = (simulation.width(), simulation.height());
+
     // Create window
     let mut window = Window::new(
         "Particle System",
-        WIDTH,
-        HEIGHT,
+        width,
+        height,
         WindowOptions::default(),
     )
-    .unwrap_or_else(|e| {
-        panic!("Window creation failed: {}", e);
-    });
+    .map_err(|e| format!("window creation failed: {}", e))?;
 
     // Set up fps cap
     window.limit_update_rate(Some(std::time::Duration::from_micros(1_000_000 / FPS_CAP)));
 
-    // Initialize simulation and renderer
-    let mut simulation = Simulation::new(WIDTH, HEIGHT);
-    let mut renderer = Renderer::new(WIDTH, HEIGHT);
+    let mut renderer = Renderer::new(width, height);
 
     // Track mouse position for gravity center
-    let mut mouse_pos = Vector2::new(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0);
+    let mut mouse_pos = Vector2::new(width as f32 / 2.0, height as f32 / 2.0);
+
+    let mut last_frame = Instant::now();
+    let mut accumulator = 0.0;
+    let mut frame = 0;
 
     // Main loop
     while window.is_open() && !window.is_key_down(Key::Escape) {
+        if options.frames.is_some_and(|frames| frame >= frames) {
+            break;
+        }
+
         // Update mouse position if available
         if let Some((x, y)) = window.get_mouse_pos(MouseMode::Clamp) {
             mouse_pos.x = x;
             mouse_pos.y = y;
-            println!("Mouse position: ({}, {})", x, y);  // Debug output
         }
+        simulation.set_input(InputState {
+            mouse_position: Some(mouse_pos),
+            left_button: window.get_mouse_down(MouseButton::Left),
+            right_button: window.get_mouse_down(MouseButton::Right),
+        });
 
-        // Update simulation
-        simulation.update(mouse_pos);
+        // Roll new random rules (e.g. Particle Life attractions)
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            simulation.randomize_pair_forces(rand::random());
+        }
+
+        // Step the simulation in fixed increments to cover the elapsed time
+        let now = Instant::now();
+        accumulator += now.duration_since(last_frame).as_secs_f32().min(MAX_FRAME_TIME);
+        last_frame = now;
+        while accumulator >= FIXED_DT {
+            simulation.step(FIXED_DT);
+            accumulator -= FIXED_DT;
+        }
 
         // Render
         let buffer = renderer.render(&simulation);
-        
+        if let Some(output) = &options.output {
+            save_frame(output, frame, &buffer, width, height)?;
+        }
+
         // Display
-        window.update_with_buffer(&buffer, WIDTH, HEIGHT)
-            .unwrap_or_else(|e| {
-                panic!("Window update failed: {}", e);
-            });
+        window.update_with_buffer(&buffer, width, height)
+            .map_err(|e| format!("window update failed: {}", e))?;
+        frame += 1;
     }
-} 
\ No newline at end of file
+
+    Ok(())
+}
+
+fn save_frame(output: &Path, frame: u64, buffer: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+    let path = output.join(format!("frame_{:05}.ppm", frame));
+    write_ppm(&path, buffer, width, height)
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    Ok(())
+}
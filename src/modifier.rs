@@ -0,0 +1,85 @@
+//! Over-lifetime modifiers.
+//!
+//! Modifiers change how particles look as they age. Each step, every
+//! registered modifier is evaluated at a particle's normalized age
+//! (`age / lifetime`, from 0 at birth to 1 at death). Particles that live
+//! forever are left alone.
+
+use crate::animation::Curve;
+
+/// The attributes of a particle a modifier may change.
+#[derive(Clone, Copy, Debug)]
+pub struct Appearance {
+    pub color: u32,
+    pub alpha: f32,
+    pub radius: f32,
+    /// Radius the particle was spawned with
+    pub spawn_radius: f32,
+}
+
+/// Changes a particle's appearance based on its normalized age.
+pub trait Modifier: Send + Sync {
+    fn apply(&self, age: f32, appearance: &mut Appearance);
+}
+
+/// Colors at points in a particle's life, blended linearly in between.
+#[derive(Clone, Debug)]
+pub struct ColorGradient {
+    stops: Vec<(f32, u32)>,
+}
+
+impl ColorGradient {
+    /// `stops` are `(normalized age, 0xrrggbb)` pairs, in any order.
+    /// Panics if there are none.
+    pub fn new(mut stops: Vec<(f32, u32)>) -> Self {
+        assert!(!stops.is_empty(), "color gradient needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    pub fn color_at(&self, age: f32) -> u32 {
+        let next = self.stops.partition_point(|&(stop, _)| stop <= age);
+        if next == 0 {
+            return self.stops[0].1;
+        }
+        if next == self.stops.len() {
+            return self.stops[next - 1].1;
+        }
+        let (start, from) = self.stops[next - 1];
+        let (end, to) = self.stops[next];
+        let t = (age - start) / (end - start);
+        [16, 8, 0].iter().fold(0, |color, &shift| {
+            let a = ((from >> shift) & 0xff) as f32;
+            let b = ((to >> shift) & 0xff) as f32;
+            color | (((a + (b - a) * t).round() as u32) << shift)
+        })
+    }
+}
+
+impl Modifier for ColorGradient {
+    fn apply(&self, age: f32, appearance: &mut Appearance) {
+        appearance.color = self.color_at(age);
+    }
+}
+
+/// Scales the particle's spawn radius by a curve over its life.
+pub struct SizeOverLifetime {
+    pub scale: Curve,
+}
+
+impl Modifier for SizeOverLifetime {
+    fn apply(&self, age: f32, appearance: &mut Appearance) {
+        appearance.radius = appearance.spawn_radius * self.scale.value_at(age).max(0.0);
+    }
+}
+
+/// Sets the particle's opacity from a curve over its life, e.g. to fade out.
+pub struct AlphaOverLifetime {
+    pub alpha: Curve,
+}
+
+impl Modifier for AlphaOverLifetime {
+    fn apply(&self, age: f32, appearance: &mut Appearance) {
+        appearance.alpha = self.alpha.value_at(age).clamp(0.0, 1.0);
+    }
+}
@@ -0,0 +1,97 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+// Gradients towards the midpoints of the edges of a cube (all of length √2),
+// as in improved Perlin noise
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+/// Most layers [`Noise::fractal`] adds up; finer ones are far below `f32`
+/// precision anyway.
+pub const MAX_OCTAVES: u32 = 16;
+
+/// Seeded 3D gradient (Perlin) noise.
+///
+/// Smooth, band-limited pseudo-random values in roughly [-1, 1], varying on
+/// a scale of about one unit. The third coordinate is typically time, so a 2D
+/// field can evolve smoothly. The same seed always gives the same noise.
+#[derive(Clone, Debug)]
+pub struct Noise {
+    permutation: [u8; 512], // Shuffled 0..=255, repeated so lookups never wrap
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let mut permutation = [0; 512];
+        for (i, entry) in permutation.iter_mut().enumerate() {
+            *entry = table[i % 256];
+        }
+        Self { permutation }
+    }
+
+    /// Noise value at a point.
+    pub fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (xi, yi, zi) = (lattice(x), lattice(y), lattice(z));
+        let (x, y, z) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        // Dot products with the gradients at the 8 corners of the cell,
+        // blended with the quintic fade curve
+        let p = &self.permutation;
+        let a = p[xi] as usize + yi;
+        let b = p[xi + 1] as usize + yi;
+        let (aa, ab) = (p[a] as usize + zi, p[a + 1] as usize + zi);
+        let (ba, bb) = (p[b] as usize + zi, p[b + 1] as usize + zi);
+
+        lerp(w,
+            lerp(v,
+                lerp(u, gradient(p[aa], x, y, z), gradient(p[ba], x - 1.0, y, z)),
+                lerp(u, gradient(p[ab], x, y - 1.0, z), gradient(p[bb], x - 1.0, y - 1.0, z))),
+            lerp(v,
+                lerp(u, gradient(p[aa + 1], x, y, z - 1.0), gradient(p[ba + 1], x - 1.0, y, z - 1.0)),
+                lerp(u, gradient(p[ab + 1], x, y - 1.0, z - 1.0), gradient(p[bb + 1], x - 1.0, y - 1.0, z - 1.0))))
+    }
+
+    /// Fractal (fBm) noise: `octaves` layers, each at twice the frequency and
+    /// half the amplitude of the previous one, normalized back to about [-1, 1].
+    /// `octaves` is clamped to `1..=MAX_OCTAVES`.
+    pub fn fractal(&self, x: f32, y: f32, z: f32, octaves: u32) -> f32 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut total_amplitude = 0.0;
+        for _ in 0..octaves.clamp(1, MAX_OCTAVES) {
+            sum += amplitude * self.sample(x * frequency, y * frequency, z * frequency);
+            total_amplitude += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        sum / total_amplitude
+    }
+}
+
+// Index of the lattice cell containing `x`, wrapped to the permutation table
+fn lattice(x: f32) -> usize {
+    (x.floor() as i64).rem_euclid(256) as usize
+}
+
+// 6t⁵ - 15t⁴ + 10t³, whose first and second derivatives vanish at 0 and 1
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn gradient(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    let [gx, gy, gz] = GRADIENTS[hash as usize % 12];
+    gx * x + gy * y + gz * z
+}
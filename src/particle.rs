@@ -1,27 +1,68 @@
+use crate::boundary::{Boundaries, WallHit};
 use nalgebra::Vector2;
 
+// Maximum speed, enforced after every step to prevent instability
+pub const MAX_VELOCITY: f32 = 1000.0;
+
+#[derive(Clone, Copy, Debug)]
 pub struct Particle {
     pub position: Vector2<f32>,
     pub velocity: Vector2<f32>,
     pub acceleration: Vector2<f32>,
     pub mass: f32,
+    pub charge: f32,
     pub radius: f32,
     pub color: u32,
-    damping: f32,         // Velocity damping coefficient
-    restitution: f32,     // Collision elasticity
+    /// Opacity from 0 (invisible) to 1 (opaque)
+    pub alpha: f32,
+    /// Group (layer) the particle belongs to, below `group::MAX_GROUPS`
+    pub group: u8,
+    /// Seconds since the particle was spawned
+    pub age: f32,
+    /// Age at which the particle is removed, `f32::INFINITY` to live forever
+    pub lifetime: f32,
+    /// Sub-emitter nesting level: 0 for particles from populations and
+    /// emitters, one more than the parent's for particles from sub-emitters
+    pub generation: u8,
+    pub(crate) restitution: f32,  // Collision elasticity
 }
 
 // State for physics calculations
 #[derive(Clone, Copy)]
 pub struct State {
-    position: Vector2<f32>,
-    velocity: Vector2<f32>,
+    pub position: Vector2<f32>,
+    pub velocity: Vector2<f32>,
+}
+
+// State derivative used by the integrators
+#[derive(Clone, Copy)]
+pub struct StateDerivative {
+    pub dp: Vector2<f32>, // derivative of position
+    pub dv: Vector2<f32>, // derivative of velocity
+}
+
+impl State {
+    // State reached by following `derivative` for `dt` seconds
+    pub fn advanced(&self, derivative: &StateDerivative, dt: f32) -> State {
+        State {
+            position: self.position + derivative.dp * dt,
+            velocity: self.velocity + derivative.dv * dt,
+        }
+    }
+}
+
+// Clamp each component of a force to keep the integration numerically stable
+pub fn limit_force(force: Vector2<f32>) -> Vector2<f32> {
+    force.map(|x| x.clamp(-1000.0, 1000.0))
 }
 
-// State derivative for RK4 integration
-struct StateDerivative {
-    dp: Vector2<f32>, // derivative of position
-    dv: Vector2<f32>, // derivative of velocity
+// Scale a velocity down to at most MAX_VELOCITY
+pub fn limit_velocity(velocity: Vector2<f32>) -> Vector2<f32> {
+    if velocity.norm() > MAX_VELOCITY {
+        velocity.normalize() * MAX_VELOCITY
+    } else {
+        velocity
+    }
 }
 
 impl Particle {
@@ -31,87 +72,38 @@ impl Particle {
             velocity: Vector2::new(0.0, 0.0),
             acceleration: Vector2::new(0.0, 0.0),
             mass,
+            charge: 0.0,
             radius,
             color,
-            damping: 0.98,      // Slight damping to simulate air resistance
+            alpha: 1.0,
+            group: 0,
+            age: 0.0,
+            lifetime: f32::INFINITY,
+            generation: 0,
             restitution: 0.8,   // Fairly elastic collisions
         }
     }
 
-    pub fn update(&mut self, dt: f32) {
-        // Apply damping to velocity
-        self.velocity *= self.damping;
-
-        // RK4 integration with improved state handling
-        let initial_state = State {
+    pub fn state(&self) -> State {
+        State {
             position: self.position,
             velocity: self.velocity,
-        };
-
-        let k1 = self.evaluate(0.0, &initial_state, &StateDerivative {
-            dp: Vector2::new(0.0, 0.0),
-            dv: Vector2::new(0.0, 0.0),
-        });
-        
-        let k2 = self.evaluate(
-            dt * 0.5,
-            &self.state_at(dt * 0.5, &initial_state, &k1),
-            &k1
-        );
-        
-        let k3 = self.evaluate(
-            dt * 0.5,
-            &self.state_at(dt * 0.5, &initial_state, &k2),
-            &k2
-        );
-        
-        let k4 = self.evaluate(
-            dt,
-            &self.state_at(dt, &initial_state, &k3),
-            &k3
-        );
-
-        // Combine derivatives with proper weights
-        let dp_dt = (k1.dp + (k2.dp + k3.dp) * 2.0 + k4.dp) * (1.0 / 6.0);
-        let dv_dt = (k1.dv + (k2.dv + k3.dv) * 2.0 + k4.dv) * (1.0 / 6.0);
-
-        // Update position and velocity
-        self.position += dp_dt * dt;
-        self.velocity += dv_dt * dt;
-        
-        // Enforce maximum velocity to prevent instability
-        let max_velocity = 1000.0;
-        if self.velocity.norm() > max_velocity {
-            self.velocity = self.velocity.normalize() * max_velocity;
         }
-
-        // Reset acceleration for next frame
-        self.acceleration = Vector2::new(0.0, 0.0);
     }
 
-    // Calculate state at a given time offset
-    fn state_at(&self, dt: f32, initial: &State, derivative: &StateDerivative) -> State {
-        State {
-            position: initial.position + derivative.dp * dt,
-            velocity: initial.velocity + derivative.dv * dt,
-        }
-    }
+    // Move the particle to its integrated state for the end of the step
+    pub fn update(&mut self, state: State) {
+        self.position = state.position;
+        // Enforce maximum velocity to prevent instability
+        self.velocity = limit_velocity(state.velocity);
 
-    // Evaluate derivatives at a given time offset and with a given state
-    fn evaluate(&self, dt: f32, state: &State, derivative: &StateDerivative) -> StateDerivative {
-        let position = state.position + derivative.dp * dt;
-        let velocity = state.velocity + derivative.dv * dt;
-        
-        StateDerivative {
-            dp: velocity,
-            dv: self.acceleration,
-        }
+        // Reset acceleration for next frame
+        self.acceleration = Vector2::new(0.0, 0.0);
     }
 
     pub fn apply_force(&mut self, force: Vector2<f32>) {
         // Apply force with improved numerical stability
-        let force = force.map(|x| x.min(1000.0).max(-1000.0)); // Limit force magnitude
-        self.acceleration += force / self.mass;
+        self.acceleration += limit_force(force) / self.mass;
     }
 
     // Get kinetic energy of the particle
@@ -119,8 +111,8 @@ impl Particle {
         0.5 * self.mass * self.velocity.norm_squared()
     }
 
-    // Handle collision with another particle
-    pub fn handle_collision(&mut self, other: &mut Particle) {
+    // Handle collision with another particle, returning whether an impulse was applied
+    pub fn handle_collision(&mut self, other: &mut Particle) -> bool {
         let diff = self.position - other.position;
         let distance = diff.norm();
         let min_distance = self.radius + other.radius;
@@ -134,7 +126,7 @@ impl Particle {
             
             // Don't resolve if particles are moving apart
             if vel_along_normal > 0.0 {
-                return;
+                return false;
             }
 
             // Calculate impulse
@@ -153,27 +145,16 @@ impl Particle {
                            (1.0/self.mass + 1.0/other.mass);
             self.position += correction / self.mass;
             other.position -= correction / other.mass;
+            return true;
         }
+        false
     }
 
-    // Handle collision with boundaries
-    pub fn handle_boundary_collision(&mut self, width: f32, height: f32) {
-        // X-axis boundaries
-        if self.position.x - self.radius < 0.0 {
-            self.position.x = self.radius;
-            self.velocity.x = -self.velocity.x * self.restitution;
-        } else if self.position.x + self.radius > width {
-            self.position.x = width - self.radius;
-            self.velocity.x = -self.velocity.x * self.restitution;
-        }
-
-        // Y-axis boundaries
-        if self.position.y - self.radius < 0.0 {
-            self.position.y = self.radius;
-            self.velocity.y = -self.velocity.y * self.restitution;
-        } else if self.position.y + self.radius > height {
-            self.position.y = height - self.radius;
-            self.velocity.y = -self.velocity.y * self.restitution;
-        }
+    // Handle collision with the edges of a `width` by `height` area, returning
+    // the wall reached, if any. Removing the particle when its policy is
+    // `BoundaryPolicy::Kill` is up to the caller.
+    pub fn handle_boundary_collision(&mut self, width: f32, height: f32, boundaries: &Boundaries) -> Option<WallHit> {
+        let bounds = Vector2::new(width, height);
+        boundaries.apply(&mut self.position, &mut self.velocity, self.radius, self.restitution, bounds)
     }
-} 
\ No newline at end of file
+}
@@ -1,5 +1,11 @@
 use crate::simulation::Simulation;
 use nalgebra::Vector2;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Largest frame width or height, in pixels.
+pub const MAX_SIZE: usize = 16384;
 
 pub struct Renderer {
     width: usize,
@@ -12,7 +18,7 @@ impl Renderer {
         Self {
             width,
             height,
-            buffer: vec![0; width * height],
+            buffer: vec![0; width.checked_mul(height).expect("frame size overflows")],
         }
     }
     
@@ -21,14 +27,17 @@ impl Renderer {
         self.buffer.fill(0);
         
         // Draw particles
-        for particle in &simulation.particles {
-            self.draw_particle(particle.position, particle.radius, particle.color);
+        for particle in simulation.particles.iter() {
+            self.draw_particle(particle.position, particle.radius, particle.color, particle.alpha);
         }
         
         self.buffer.clone()
     }
     
-    fn draw_particle(&mut self, position: Vector2<f32>, radius: f32, color: u32) {
+    fn draw_particle(&mut self, position: Vector2<f32>, radius: f32, color: u32, alpha: f32) {
+        if alpha <= 0.0 {
+            return;
+        }
         let x_start = (position.x - radius).max(0.0) as usize;
         let y_start = (position.y - radius).max(0.0) as usize;
         let x_end = (position.x + radius).min(self.width as f32 - 1.0) as usize;
@@ -41,10 +50,29 @@ impl Renderer {
                 if dx*dx + dy*dy <= radius*radius {
                     let idx = y * self.width + x;
                     if idx < self.buffer.len() {
-                        self.buffer[idx] = color;
+                        self.buffer[idx] = if alpha >= 1.0 { color } else { blend(self.buffer[idx], color, alpha) };
                     }
                 }
             }
         }
     }
-} 
\ No newline at end of file
+}
+
+// Mix `color` over `background` with the given opacity, per channel
+fn blend(background: u32, color: u32, alpha: f32) -> u32 {
+    [16, 8, 0].iter().fold(0, |pixel, &shift| {
+        let below = ((background >> shift) & 0xff) as f32;
+        let above = ((color >> shift) & 0xff) as f32;
+        pixel | (((below + (above - below) * alpha).round() as u32) << shift)
+    })
+}
+
+/// Writes a `0x00rrggbb` pixel buffer as a binary PPM image.
+pub fn write_ppm(path: impl AsRef<Path>, buffer: &[u32], width: usize, height: usize) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    for &pixel in &buffer[..width * height] {
+        out.write_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])?;
+    }
+    out.flush()
+}
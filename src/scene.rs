@@ -0,0 +1,1115 @@
+//! Scene description files.
+//!
+//! A scene is a TOML document declaring the simulation bounds, the initial
+//! particle populations, the forces acting on them and the forces between them:
+//!
+//! ```toml
+//! seed = 42
+//! integrator = "rk4"
+//!
+//! [bounds]
+//! width = 800
+//! height = 600
+//!
+//! [[pair_forces]]
+//! type = "linear_spring"
+//! equilibrium_distance = 50.0
+//! coefficient = 0.05
+//!
+//! [[populations]]
+//! count = 50
+//! mass = [0.5, 2.0]
+//!
+//! [[forces]]
+//! type = "gravity"
+//! strength = 2.0
+//!
+//! [[forces]]
+//! type = "mouse_gravity"
+//! strength = 5000.0
+//! ```
+//!
+//! Wherever a number is drawn per particle it may be given either as a fixed
+//! value or as a `[min, max]` range sampled uniformly.
+
+use crate::animation::{Animated, Curve, Interpolation, Keyframe, Waveform};
+use crate::boundary::{Boundaries, BoundaryPolicy};
+use crate::emitter::{Burst, Emitter, EmitterShape, SubEmitter, Trigger};
+use crate::field::VectorGrid;
+use crate::force::{
+    Attractor, Falloff, Force, Gravity, LinearDrag, MagneticField, MouseGravity, QuadraticDrag, Repulsor, Turbulence,
+    VectorField, Vortex, Wind,
+};
+use crate::group::{GroupMask, GroupMatrix, MAX_GROUPS};
+use crate::integrator::{ExplicitEuler, Integrator, Leapfrog, Rk4, SemiImplicitEuler, VelocityVerlet};
+use crate::interaction::{
+    Coulomb, LennardJones, LinearSpring, Morse, PairForce, PairSolver, ParticleLife, SoftRepulsion,
+};
+use crate::modifier::{AlphaOverLifetime, ColorGradient, Modifier, SizeOverLifetime};
+use crate::noise::{Noise, MAX_OCTAVES};
+use crate::particle::Particle;
+use crate::renderer::MAX_SIZE;
+use crate::simulation::Simulation;
+use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The built-in scene, used by [`Simulation::new`] and when the binary is run
+/// without `--scene`.
+pub const DEFAULT_SCENE: &str = include_str!("../scenes/default.toml");
+
+/// Error raised while loading or building a scene.
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "could not read scene: {}", e),
+            SceneError::Parse(e) => write!(f, "could not parse scene: {}", e),
+            SceneError::Invalid(message) => write!(f, "invalid scene: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Io(e) => Some(e),
+            SceneError::Parse(e) => Some(e),
+            SceneError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneError {
+    fn from(e: std::io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for SceneError {
+    fn from(e: toml::de::Error) -> Self {
+        SceneError::Parse(e)
+    }
+}
+
+/// A number that is either fixed or drawn uniformly from `[min, max]`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Fixed(f32),
+    Range([f32; 2]),
+}
+
+impl Value {
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        match *self {
+            Value::Fixed(value) => value,
+            Value::Range([min, max]) if min < max => rng.gen_range(min..max),
+            Value::Range([min, _]) => min,
+        }
+    }
+
+    fn validate(&self, name: &str) -> Result<(), SceneError> {
+        match *self {
+            Value::Fixed(value) if !value.is_finite() => {
+                Err(SceneError::Invalid(format!("`{}` must be finite, got {}", name, value)))
+            }
+            // The width must be finite too, or sampling the range overflows
+            Value::Range([min, max]) if !(max - min).is_finite() => {
+                Err(SceneError::Invalid(format!("`{}` range [{}, {}] must be finite", name, min, max)))
+            }
+            Value::Range([min, max]) if min > max => {
+                Err(SceneError::Invalid(format!("`{}` range [{}, {}] is reversed", name, min, max)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn range(&self) -> RangeInclusive<f32> {
+        match *self {
+            Value::Fixed(value) => value..=value,
+            Value::Range([min, max]) => min..=max,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bounds {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+        }
+    }
+}
+
+/// Policy for each edge of the bounds: "reflect", "stick", "kill" (or
+/// "absorb") or "open". Edges reflect when omitted.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BoundariesDesc {
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub top: Option<String>,
+    pub bottom: Option<String>,
+    /// Slowest arrival that counts as a hit for `boundary` sub-emitters.
+    pub min_impact_speed: Option<f32>,
+}
+
+impl BoundariesDesc {
+    pub fn build(&self) -> Result<Boundaries, SceneError> {
+        let policy = |name: &Option<String>| match name {
+            Some(name) => boundary_policy_named(name),
+            None => Ok(BoundaryPolicy::Reflect),
+        };
+        Ok(Boundaries {
+            left: policy(&self.left)?,
+            right: policy(&self.right)?,
+            top: policy(&self.top)?,
+            bottom: policy(&self.bottom)?,
+            min_impact_speed: match self.min_impact_speed {
+                Some(speed) => non_negative("min_impact_speed", speed)?,
+                None => Boundaries::default().min_impact_speed,
+            },
+        })
+    }
+}
+
+/// Rectangular area particles are spawned in.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+/// A group of particles created when the scene is built.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Population {
+    pub count: usize,
+    pub mass: Value,
+    /// Defaults to twice the mass.
+    pub radius: Option<Value>,
+    #[serde(default = "zero")]
+    pub charge: Value,
+    /// Initial velocity components.
+    #[serde(default = "zero_velocity")]
+    pub velocity: [Value; 2],
+    /// `"#rrggbb"`; random per particle when omitted.
+    pub color: Option<String>,
+    /// Defaults to the whole scene.
+    pub region: Option<Region>,
+    #[serde(default)]
+    pub group: u8,
+    /// Seconds each particle lives; forever when omitted.
+    pub lifetime: Option<Value>,
+}
+
+fn zero() -> Value {
+    Value::Fixed(0.0)
+}
+
+fn zero_velocity() -> [Value; 2] {
+    [Value::Fixed(0.0), Value::Fixed(0.0)]
+}
+
+/// An external force and its parameters, selected by `type`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ForceDesc {
+    Gravity {
+        #[serde(default = "default_gravity_strength")]
+        strength: f32,
+        #[serde(default = "down")]
+        direction: [f32; 2],
+    },
+    Wind {
+        strength: f32,
+        direction: [f32; 2],
+    },
+    MouseGravity {
+        #[serde(default = "default_mouse_gravity_strength")]
+        strength: f32,
+    },
+    MagneticField {
+        strength: f32,
+    },
+    LinearDrag {
+        coefficient: f32,
+    },
+    QuadraticDrag {
+        coefficient: f32,
+    },
+    Turbulence {
+        scale: f32,
+        strength: f32,
+        #[serde(default = "default_turbulence_octaves")]
+        octaves: u32,
+        #[serde(default = "default_turbulence_speed")]
+        speed: f32,
+        #[serde(default)]
+        seed: u64,
+    },
+    VectorField {
+        /// Text grid or PPM image, relative to the scene file
+        path: PathBuf,
+        strength: f32,
+    },
+    Attractor(PointSource),
+    /// An attractor pushing particles away
+    Repulsor(PointSource),
+    Vortex(PointSource),
+}
+
+/// Parameters shared by the forces anchored at a point.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointSource {
+    pub position: [f32; 2],
+    pub strength: f32,
+    /// "constant", "linear", "inverse", "inverse_square" or "smoothstep".
+    /// Defaults to "inverse_square".
+    pub falloff: Option<String>,
+    #[serde(default)]
+    pub softening: f32,
+    /// Unlimited if absent.
+    pub max_radius: Option<f32>,
+}
+
+impl PointSource {
+    // Falloff, softening and max radius, validated
+    fn reach(&self) -> Result<(Falloff, f32, f32), SceneError> {
+        let falloff = match &self.falloff {
+            Some(name) => falloff_named(name)?,
+            None => Falloff::InverseSquare,
+        };
+        let max_radius = match self.max_radius {
+            Some(radius) => positive("max_radius", radius)?,
+            None if matches!(falloff, Falloff::Linear | Falloff::Smoothstep) => {
+                return Err(SceneError::Invalid("linear and smoothstep falloffs require max_radius".to_string()));
+            }
+            None => f32::INFINITY,
+        };
+        Ok((falloff, non_negative("softening", self.softening)?, max_radius))
+    }
+
+    fn attractor(&self) -> Result<Attractor, SceneError> {
+        let (falloff, softening, max_radius) = self.reach()?;
+        Ok(Attractor {
+            position: finite_vector("position", self.position)?,
+            strength: finite("strength", self.strength)?,
+            falloff,
+            softening,
+            max_radius,
+        })
+    }
+}
+
+fn default_gravity_strength() -> f32 {
+    Gravity::default().strength
+}
+
+fn down() -> [f32; 2] {
+    [0.0, 1.0]
+}
+
+fn default_mouse_gravity_strength() -> f32 {
+    MouseGravity::default().strength
+}
+
+fn default_turbulence_octaves() -> u32 {
+    Turbulence::new(1.0, 0.0).octaves
+}
+
+fn default_turbulence_speed() -> f32 {
+    Turbulence::new(1.0, 0.0).speed
+}
+
+impl ForceDesc {
+    pub fn build(&self) -> Result<Box<dyn Force>, SceneError> {
+        Ok(match *self {
+            ForceDesc::Gravity { strength, direction } => Box::new(Gravity {
+                strength: finite("gravity strength", strength)?,
+                direction: finite_vector("gravity direction", direction)?,
+            }),
+            ForceDesc::Wind { strength, direction } => Box::new(Wind {
+                strength: finite("wind strength", strength)?,
+                direction: finite_vector("wind direction", direction)?,
+            }),
+            ForceDesc::MouseGravity { strength } => Box::new(MouseGravity {
+                strength: finite("mouse_gravity strength", strength)?,
+            }),
+            ForceDesc::MagneticField { strength } => Box::new(MagneticField {
+                strength: finite("magnetic_field strength", strength)?,
+            }),
+            ForceDesc::LinearDrag { coefficient } => Box::new(LinearDrag {
+                coefficient: non_negative("linear_drag coefficient", coefficient)?,
+            }),
+            ForceDesc::QuadraticDrag { coefficient } => Box::new(QuadraticDrag {
+                coefficient: non_negative("quadratic_drag coefficient", coefficient)?,
+            }),
+            ForceDesc::Turbulence { scale, strength, octaves, speed, seed } => Box::new(Turbulence {
+                noise: Noise::new(seed),
+                scale: positive("turbulence scale", scale)?,
+                strength: finite("turbulence strength", strength)?,
+                octaves: if (1..=MAX_OCTAVES).contains(&octaves) {
+                    octaves
+                } else {
+                    return Err(SceneError::Invalid(format!(
+                        "turbulence octaves must be between 1 and {}, got {}",
+                        MAX_OCTAVES, octaves
+                    )));
+                },
+                speed: finite("turbulence speed", speed)?,
+            }),
+            ForceDesc::VectorField { ref path, strength } => Box::new(VectorField {
+                grid: VectorGrid::load(path)
+                    .map_err(|e| SceneError::Invalid(format!("vector field {}: {}", path.display(), e)))?,
+                strength: finite("vector_field strength", strength)?,
+            }),
+            ForceDesc::Attractor(ref source) => Box::new(source.attractor()?),
+            ForceDesc::Repulsor(ref source) => Box::new(Repulsor(source.attractor()?)),
+            ForceDesc::Vortex(ref source) => {
+                let (falloff, softening, max_radius) = source.reach()?;
+                Box::new(Vortex {
+                    position: finite_vector("position", source.position)?,
+                    strength: finite("strength", source.strength)?,
+                    falloff,
+                    softening,
+                    max_radius,
+                })
+            }
+        })
+    }
+}
+
+/// A source of particles spawned while the simulation runs.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmitterDesc {
+    /// Relative to the parent for a sub-emitter's emitter.
+    #[serde(default)]
+    pub position: [f32; 2],
+    #[serde(default)]
+    pub shape: ShapeDesc,
+    /// Particles per second.
+    #[serde(default)]
+    pub rate: f32,
+    #[serde(default)]
+    pub bursts: Vec<BurstDesc>,
+    /// Direction of the velocity cone, in degrees clockwise from +x (-90 is up).
+    #[serde(default = "up")]
+    pub angle: f32,
+    /// Width of the velocity cone in degrees.
+    #[serde(default)]
+    pub spread: f32,
+    #[serde(default = "zero")]
+    pub speed: Value,
+    #[serde(default = "one")]
+    pub mass: Value,
+    /// Defaults to twice the mass.
+    pub radius: Option<Value>,
+    /// `"#rrggbb"` colors picked at random; random colors when empty.
+    #[serde(default)]
+    pub colors: Vec<String>,
+    #[serde(default)]
+    pub group: u8,
+    /// Seconds each particle lives; forever when omitted.
+    pub lifetime: Option<Value>,
+}
+
+/// Area an emitter spawns particles in, selected by `type`.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ShapeDesc {
+    #[default]
+    Point,
+    /// From the emitter's position to `end`.
+    Line {
+        end: [f32; 2],
+    },
+    Circle {
+        radius: f32,
+    },
+    Box {
+        width: f32,
+        height: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BurstDesc {
+    pub time: f32,
+    pub count: usize,
+    /// Repeat every `interval` seconds.
+    pub interval: Option<f32>,
+}
+
+fn up() -> f32 {
+    -90.0
+}
+
+fn one() -> Value {
+    Value::Fixed(1.0)
+}
+
+impl EmitterDesc {
+    pub fn build(&self) -> Result<Emitter, SceneError> {
+        for (name, value) in [("speed", &self.speed), ("mass", &self.mass)] {
+            value.validate(name)?;
+        }
+        if *self.mass.range().start() <= 0.0 {
+            return Err(SceneError::Invalid("emitter mass must be positive".to_string()));
+        }
+        if let Some(radius) = &self.radius {
+            radius.validate("radius")?;
+        }
+
+        let position = finite_vector("emitter position", self.position)?;
+        let mut emitter = Emitter::new(position);
+        emitter.shape = match self.shape {
+            ShapeDesc::Point => EmitterShape::Point,
+            ShapeDesc::Line { end } => EmitterShape::Line {
+                offset: finite_vector("emitter line end", end)? - position,
+            },
+            ShapeDesc::Circle { radius } => EmitterShape::Circle {
+                radius: non_negative("emitter circle radius", radius)?,
+            },
+            ShapeDesc::Box { width, height } => EmitterShape::Box {
+                width: non_negative("emitter box width", width)?,
+                height: non_negative("emitter box height", height)?,
+            },
+        };
+        emitter.rate = non_negative("emitter rate", self.rate)?;
+        for burst in &self.bursts {
+            if let Some(interval) = burst.interval {
+                positive("burst interval", interval)?;
+            }
+            emitter.bursts.push(Burst {
+                time: finite("burst time", burst.time)?,
+                count: burst.count,
+                interval: burst.interval,
+            });
+        }
+        emitter.angle = finite("emitter angle", self.angle)?;
+        emitter.spread = non_negative("emitter spread", self.spread)?;
+        emitter.speed = self.speed.range();
+        emitter.mass = self.mass.range();
+        emitter.radius = self.radius.map(|radius| radius.range());
+        emitter.colors = self.colors.iter().map(|color| parse_color(color)).collect::<Result<_, _>>()?;
+        emitter.group = group_index(self.group)?;
+        if let Some(lifetime) = &self.lifetime {
+            lifetime.validate("lifetime")?;
+            emitter.lifetime = Some(lifetime.range());
+        }
+        Ok(emitter)
+    }
+}
+
+/// Spawns children from particles of `parents` when `trigger` happens to them.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubEmitterDesc {
+    /// "death", "boundary" or "collision".
+    pub trigger: String,
+    /// Groups whose particles fire the sub-emitter; all of them when omitted.
+    pub parents: Option<Vec<u8>>,
+    /// Children per trigger.
+    pub count: usize,
+    /// Fraction of the parent's velocity each child inherits.
+    #[serde(default = "default_inherit_velocity")]
+    pub inherit_velocity: f32,
+    /// Emitter the children are drawn from, without a rate or bursts.
+    pub emitter: EmitterDesc,
+}
+
+fn default_inherit_velocity() -> f32 {
+    1.0
+}
+
+impl SubEmitterDesc {
+    pub fn build(&self) -> Result<SubEmitter, SceneError> {
+        let trigger = match self.trigger.as_str() {
+            "death" => Trigger::Death,
+            "boundary" => Trigger::Boundary,
+            "collision" => Trigger::Collision,
+            name => return Err(SceneError::Invalid(format!("unknown sub-emitter trigger `{}`", name))),
+        };
+        if self.emitter.rate != 0.0 || !self.emitter.bursts.is_empty() {
+            return Err(SceneError::Invalid("sub-emitters spawn `count` children per trigger, not a rate or bursts".to_string()));
+        }
+        let mut sub_emitter = SubEmitter::new(trigger, self.emitter.build()?);
+        sub_emitter.parents = group_mask(&self.parents)?;
+        sub_emitter.count = self.count;
+        sub_emitter.inherit_velocity = finite("inherit_velocity", self.inherit_velocity)?;
+        Ok(sub_emitter)
+    }
+}
+
+/// One `[[forces]]` entry: a force, optionally with parameters animated over time.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ForceEntry {
+    #[serde(flatten)]
+    pub force: ForceDesc,
+    /// Curves driving the force's parameters, by parameter name.
+    #[serde(default)]
+    pub animate: BTreeMap<String, CurveDesc>,
+    /// Groups the force applies to; all of them when omitted.
+    pub groups: Option<Vec<u8>>,
+}
+
+impl ForceEntry {
+    pub fn build(&self) -> Result<Box<dyn Force>, SceneError> {
+        let force = self.force.build()?;
+        if self.animate.is_empty() {
+            return Ok(force);
+        }
+
+        let mut animated = Animated::new(force);
+        for (parameter, curve) in &self.animate {
+            if !animated.animate(parameter, curve.build()?) {
+                return Err(SceneError::Invalid(format!("force has no parameter `{}` to animate", parameter)));
+            }
+        }
+        Ok(Box::new(animated))
+    }
+}
+
+/// A curve driving a force parameter or a modifier, selected by `curve`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "curve", rename_all = "snake_case", deny_unknown_fields)]
+pub enum CurveDesc {
+    Keyframes {
+        /// `[time, value]` pairs.
+        keys: Vec<[f32; 2]>,
+        /// "step", "linear" or "cubic". Defaults to "linear".
+        interpolation: Option<String>,
+        #[serde(default)]
+        looped: bool,
+    },
+    Sine(OscillatorDesc),
+    Triangle(OscillatorDesc),
+    Square(OscillatorDesc),
+    Sawtooth(OscillatorDesc),
+}
+
+/// Parameters shared by the periodic curves.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OscillatorDesc {
+    #[serde(default)]
+    pub center: f32,
+    pub amplitude: f32,
+    /// Cycles per second.
+    pub frequency: f32,
+    /// Fraction of a cycle.
+    #[serde(default)]
+    pub phase: f32,
+}
+
+impl CurveDesc {
+    pub fn build(&self) -> Result<Curve, SceneError> {
+        let oscillator = |waveform, desc: &OscillatorDesc| -> Result<Curve, SceneError> {
+            Ok(Curve::Oscillator {
+                waveform,
+                center: finite("oscillator center", desc.center)?,
+                amplitude: finite("oscillator amplitude", desc.amplitude)?,
+                frequency: finite("oscillator frequency", desc.frequency)?,
+                phase: finite("oscillator phase", desc.phase)?,
+            })
+        };
+        Ok(match self {
+            CurveDesc::Keyframes { keys, interpolation, looped } => {
+                if keys.is_empty() {
+                    return Err(SceneError::Invalid("keyframe curve has no keys".to_string()));
+                }
+                let interpolation = match interpolation.as_deref() {
+                    None | Some("linear") => Interpolation::Linear,
+                    Some("step") => Interpolation::Step,
+                    Some("cubic") => Interpolation::Cubic,
+                    Some(name) => return Err(SceneError::Invalid(format!("unknown interpolation `{}`", name))),
+                };
+                let keys = keys
+                    .iter()
+                    .map(|&[time, value]| {
+                        Ok(Keyframe {
+                            time: finite("key time", time)?,
+                            value: finite("key value", value)?,
+                        })
+                    })
+                    .collect::<Result<_, SceneError>>()?;
+                Curve::keyframes(keys, interpolation, *looped)
+            }
+            CurveDesc::Sine(desc) => oscillator(Waveform::Sine, desc)?,
+            CurveDesc::Triangle(desc) => oscillator(Waveform::Triangle, desc)?,
+            CurveDesc::Square(desc) => oscillator(Waveform::Square, desc)?,
+            CurveDesc::Sawtooth(desc) => oscillator(Waveform::Sawtooth, desc)?,
+        })
+    }
+}
+
+/// One `[[modifiers]]` entry: a modifier, optionally limited to some groups.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ModifierEntry {
+    #[serde(flatten)]
+    pub modifier: ModifierDesc,
+    /// Groups the modifier applies to; all of them when omitted.
+    pub groups: Option<Vec<u8>>,
+}
+
+/// A change to particles' appearance over their lifetime, selected by `type`.
+/// Curves and stops run over the normalized age, from 0 at birth to 1 at death.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ModifierDesc {
+    ColorGradient {
+        /// `[age, "#rrggbb"]` pairs.
+        stops: Vec<(f32, String)>,
+    },
+    Size {
+        /// Multiplier of the radius the particle was spawned with.
+        scale: CurveDesc,
+    },
+    Alpha {
+        alpha: CurveDesc,
+    },
+}
+
+impl ModifierDesc {
+    pub fn build(&self) -> Result<Box<dyn Modifier>, SceneError> {
+        Ok(match self {
+            ModifierDesc::ColorGradient { stops } => {
+                if stops.is_empty() {
+                    return Err(SceneError::Invalid("color gradient has no stops".to_string()));
+                }
+                let stops = stops.iter()
+                    .map(|(age, color)| Ok((finite("color gradient age", *age)?, parse_color(color)?)))
+                    .collect::<Result<_, SceneError>>()?;
+                Box::new(ColorGradient::new(stops))
+            }
+            ModifierDesc::Size { scale } => Box::new(SizeOverLifetime { scale: scale.build()? }),
+            ModifierDesc::Alpha { alpha } => Box::new(AlphaOverLifetime { alpha: alpha.build()? }),
+        })
+    }
+}
+
+/// One `[[pair_forces]]` entry: a pair force, optionally limited to some groups.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PairForceEntry {
+    #[serde(flatten)]
+    pub force: PairForceDesc,
+    /// N×N matrix where `matrix[a][b]` says whether group `b` acts on group
+    /// `a`; groups outside the matrix are unaffected. All groups interact when omitted.
+    pub matrix: Option<Vec<Vec<bool>>>,
+}
+
+/// A force between pairs of particles and its parameters, selected by `type`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PairForceDesc {
+    LinearSpring {
+        equilibrium_distance: f32,
+        coefficient: f32,
+        cutoff: Option<f32>,
+    },
+    LennardJones {
+        epsilon: f32,
+        sigma: f32,
+        /// Defaults to 2.5 sigma.
+        cutoff: Option<f32>,
+    },
+    Morse {
+        depth: f32,
+        width: f32,
+        equilibrium_distance: f32,
+        cutoff: f32,
+    },
+    SoftRepulsion {
+        strength: f32,
+        radius: f32,
+    },
+    Coulomb {
+        strength: f32,
+        #[serde(default)]
+        softening: f32,
+    },
+    /// Species are particle groups 0 to `species - 1`.
+    ParticleLife {
+        species: usize,
+        radius: f32,
+        strength: f32,
+        #[serde(default = "default_particle_life_repulsion")]
+        repulsion: f32,
+        /// `attraction[a][b]` is how strongly species `a` is drawn to species `b`.
+        /// Drawn at random from the scene seed when omitted.
+        attraction: Option<Vec<Vec<f32>>>,
+    },
+}
+
+fn default_particle_life_repulsion() -> f32 {
+    ParticleLife::new(0, 1.0, 0.0).repulsion
+}
+
+impl PairForceDesc {
+    pub fn build(&self) -> Result<Box<dyn PairForce>, SceneError> {
+        Ok(match *self {
+            PairForceDesc::LinearSpring { equilibrium_distance, coefficient, cutoff } => {
+                if let Some(cutoff) = cutoff {
+                    positive("linear_spring cutoff", cutoff)?;
+                }
+                Box::new(LinearSpring {
+                    equilibrium_distance: finite("linear_spring equilibrium_distance", equilibrium_distance)?,
+                    coefficient: finite("linear_spring coefficient", coefficient)?,
+                    cutoff,
+                })
+            }
+            PairForceDesc::LennardJones { epsilon, sigma, cutoff } => {
+                positive("lennard_jones sigma", sigma)?;
+                let mut lennard_jones = LennardJones::new(finite("lennard_jones epsilon", epsilon)?, sigma);
+                if let Some(cutoff) = cutoff {
+                    lennard_jones.cutoff = positive("lennard_jones cutoff", cutoff)?;
+                }
+                Box::new(lennard_jones)
+            }
+            PairForceDesc::Morse { depth, width, equilibrium_distance, cutoff } => Box::new(Morse {
+                depth: finite("morse depth", depth)?,
+                width: finite("morse width", width)?,
+                equilibrium_distance: finite("morse equilibrium_distance", equilibrium_distance)?,
+                cutoff: positive("morse cutoff", cutoff)?,
+            }),
+            PairForceDesc::SoftRepulsion { strength, radius } => Box::new(SoftRepulsion {
+                strength: finite("soft_repulsion strength", strength)?,
+                radius: positive("soft_repulsion radius", radius)?,
+            }),
+            PairForceDesc::Coulomb { strength, softening } => Box::new(Coulomb {
+                strength: finite("coulomb strength", strength)?,
+                softening: non_negative("coulomb softening", softening)?,
+            }),
+            PairForceDesc::ParticleLife { species, radius, strength, repulsion, ref attraction } => {
+                if species == 0 || species > MAX_GROUPS {
+                    return Err(SceneError::Invalid(format!("particle_life needs 1 to {} species", MAX_GROUPS)));
+                }
+                if !(0.0..1.0).contains(&repulsion) {
+                    return Err(SceneError::Invalid(format!("particle_life repulsion must be in [0, 1), got {}", repulsion)));
+                }
+                let mut rules = ParticleLife::new(
+                    species,
+                    positive("particle_life radius", radius)?,
+                    finite("particle_life strength", strength)?,
+                );
+                rules.repulsion = repulsion;
+                if let Some(rows) = attraction {
+                    if rows.len() != species || rows.iter().any(|row| row.len() != species) {
+                        return Err(SceneError::Invalid(format!(
+                            "particle_life attraction must be a {}x{} matrix", species, species
+                        )));
+                    }
+                    for (a, row) in rows.iter().enumerate() {
+                        for (b, &value) in row.iter().enumerate() {
+                            rules.set_attraction(a, b, finite("particle_life attraction", value)?);
+                        }
+                    }
+                }
+                Box::new(rules)
+            }
+        })
+    }
+}
+
+fn positive(name: &str, value: f32) -> Result<f32, SceneError> {
+    if value > 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(SceneError::Invalid(format!("{} must be positive and finite, got {}", name, value)))
+    }
+}
+
+fn finite(name: &str, value: f32) -> Result<f32, SceneError> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(SceneError::Invalid(format!("{} must be finite, got {}", name, value)))
+    }
+}
+
+fn finite_vector(name: &str, [x, y]: [f32; 2]) -> Result<Vector2<f32>, SceneError> {
+    Ok(Vector2::new(finite(name, x)?, finite(name, y)?))
+}
+
+fn non_negative(name: &str, value: f32) -> Result<f32, SceneError> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(SceneError::Invalid(format!("{} must be non-negative and finite, got {}", name, value)))
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    /// Seed for the initial particles; random when omitted.
+    pub seed: Option<u64>,
+    /// One of `euler`, `semi_implicit_euler`, `verlet`, `leapfrog`, `rk4`.
+    pub integrator: Option<String>,
+    /// Use Barnes-Hut with this opening angle for inter-particle forces.
+    pub barnes_hut_theta: Option<f32>,
+    #[serde(default)]
+    pub bounds: Bounds,
+    #[serde(default)]
+    pub boundaries: BoundariesDesc,
+    #[serde(default)]
+    pub pair_forces: Vec<PairForceEntry>,
+    #[serde(default)]
+    pub populations: Vec<Population>,
+    #[serde(default)]
+    pub forces: Vec<ForceEntry>,
+    #[serde(default)]
+    pub emitters: Vec<EmitterDesc>,
+    #[serde(default)]
+    pub sub_emitters: Vec<SubEmitterDesc>,
+    /// Levels of sub-emitters that can nest, see [`Simulation::set_sub_emitter_depth`].
+    pub sub_emitter_depth: Option<u8>,
+    #[serde(default)]
+    pub modifiers: Vec<ModifierEntry>,
+    /// N×N matrix of which groups collide, like a pair force `matrix`.
+    /// Every group collides with every group when omitted.
+    pub collisions: Option<Vec<Vec<bool>>>,
+}
+
+impl FromStr for Scene {
+    type Err = SceneError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(toml::from_str(text)?)
+    }
+}
+
+impl Scene {
+    /// Reads and parses a scene file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let mut scene: Self = std::fs::read_to_string(path)?.parse()?;
+        // Files referenced by the scene are relative to it
+        if let Some(directory) = path.parent() {
+            for entry in &mut scene.forces {
+                if let ForceDesc::VectorField { path, .. } = &mut entry.force {
+                    *path = directory.join(&*path);
+                }
+            }
+        }
+        Ok(scene)
+    }
+
+    /// Builds a simulation from the scene, checking that all parameters are usable.
+    pub fn build(&self) -> Result<Simulation, SceneError> {
+        let Bounds { width, height } = self.bounds;
+        if width == 0 || height == 0 {
+            return Err(SceneError::Invalid(format!("bounds {}x{} are empty", width, height)));
+        }
+        // Frames are rendered at the size of the bounds
+        if width > MAX_SIZE || height > MAX_SIZE || width.checked_mul(height).is_none() {
+            return Err(SceneError::Invalid(format!(
+                "bounds {}x{} are too large, at most {} on each side",
+                width, height, MAX_SIZE
+            )));
+        }
+
+        let mut simulation = Simulation::empty(width, height);
+        simulation.set_boundaries(self.boundaries.build()?);
+        let mut rng = StdRng::seed_from_u64(self.seed.unwrap_or_else(rand::random));
+
+        if let Some(name) = &self.integrator {
+            simulation.set_integrator(integrator_named(name)?);
+        }
+        if let Some(theta) = self.barnes_hut_theta {
+            if theta.is_nan() || theta < 0.0 {
+                return Err(SceneError::Invalid(format!("barnes_hut_theta must be non-negative, got {}", theta)));
+            }
+            simulation.set_pair_solver(PairSolver::BarnesHut { theta });
+        }
+        for entry in &self.pair_forces {
+            let groups = match &entry.matrix {
+                Some(rows) => group_matrix("pair force matrix", rows)?,
+                None => GroupMatrix::ALL,
+            };
+            let mut pair_force = entry.force.build()?;
+            if let PairForceDesc::ParticleLife { attraction: None, .. } = entry.force {
+                pair_force.randomize(&mut rng);
+            }
+            simulation.add_pair_force_between(pair_force, groups);
+        }
+        if let Some(rows) = &self.collisions {
+            let collisions = group_matrix("collisions", rows)?;
+            if (0..rows.len() as u8).any(|a| (0..a).any(|b| collisions.get(a, b) != collisions.get(b, a))) {
+                return Err(SceneError::Invalid("collisions matrix must be symmetric".to_string()));
+            }
+            simulation.set_collision_matrix(collisions);
+        }
+
+        for population in &self.populations {
+            population.spawn(&mut simulation, &mut rng)?;
+        }
+        for emitter in &self.emitters {
+            simulation.add_emitter(emitter.build()?);
+        }
+        for sub_emitter in &self.sub_emitters {
+            simulation.add_sub_emitter(sub_emitter.build()?);
+        }
+        if let Some(depth) = self.sub_emitter_depth {
+            simulation.set_sub_emitter_depth(depth);
+        }
+        simulation.set_seed(rng.gen());
+        for entry in &self.forces {
+            simulation.add_force_to_groups(entry.build()?, group_mask(&entry.groups)?);
+        }
+        for entry in &self.modifiers {
+            simulation.add_modifier(entry.modifier.build()?, group_mask(&entry.groups)?);
+        }
+
+        Ok(simulation)
+    }
+}
+
+impl Population {
+    fn spawn(&self, simulation: &mut Simulation, rng: &mut StdRng) -> Result<(), SceneError> {
+        self.mass.validate("mass")?;
+        self.charge.validate("charge")?;
+        group_index(self.group)?;
+        if let Some(radius) = &self.radius {
+            radius.validate("radius")?;
+        }
+        for component in &self.velocity {
+            component.validate("velocity")?;
+        }
+        if let Some(lifetime) = &self.lifetime {
+            lifetime.validate("lifetime")?;
+        }
+        let color = self.color.as_deref().map(parse_color).transpose()?;
+        let region = self.region.unwrap_or(Region {
+            min: [0.0, 0.0],
+            max: [simulation.width() as f32, simulation.height() as f32],
+        });
+        for axis in 0..2 {
+            Value::Range([region.min[axis], region.max[axis]]).validate("region")?;
+        }
+
+        for _ in 0..self.count {
+            let x = Value::Range([region.min[0], region.max[0]]).sample(rng);
+            let y = Value::Range([region.min[1], region.max[1]]).sample(rng);
+            let mass = self.mass.sample(rng);
+            if mass <= 0.0 {
+                return Err(SceneError::Invalid(format!("mass must be positive, got {}", mass)));
+            }
+            let radius = match &self.radius {
+                Some(radius) => radius.sample(rng),
+                None => mass * 2.0,
+            };
+            let color = color.unwrap_or_else(|| {
+                let r = rng.gen_range(0..255);
+                let g = rng.gen_range(0..255);
+                let b = rng.gen_range(0..255);
+                (r << 16) | (g << 8) | b
+            });
+
+            let mut particle = Particle::new(x, y, mass, radius, color);
+            particle.charge = self.charge.sample(rng);
+            particle.group = self.group;
+            particle.velocity = Vector2::new(self.velocity[0].sample(rng), self.velocity[1].sample(rng));
+            if let Some(lifetime) = &self.lifetime {
+                particle.lifetime = lifetime.sample(rng);
+            }
+            simulation.particles.push(particle);
+        }
+        Ok(())
+    }
+}
+
+fn integrator_named(name: &str) -> Result<Box<dyn Integrator>, SceneError> {
+    Ok(match name {
+        "euler" => Box::new(ExplicitEuler),
+        "semi_implicit_euler" => Box::new(SemiImplicitEuler),
+        "verlet" => Box::new(VelocityVerlet),
+        "leapfrog" => Box::new(Leapfrog),
+        "rk4" => Box::new(Rk4),
+        _ => return Err(SceneError::Invalid(format!("unknown integrator `{}`", name))),
+    })
+}
+
+fn boundary_policy_named(name: &str) -> Result<BoundaryPolicy, SceneError> {
+    Ok(match name {
+        "reflect" => BoundaryPolicy::Reflect,
+        "stick" => BoundaryPolicy::Stick,
+        "kill" | "absorb" => BoundaryPolicy::Kill,
+        "open" => BoundaryPolicy::Open,
+        _ => return Err(SceneError::Invalid(format!("unknown boundary policy `{}`", name))),
+    })
+}
+
+fn group_index(group: u8) -> Result<u8, SceneError> {
+    if (group as usize) < MAX_GROUPS {
+        Ok(group)
+    } else {
+        Err(SceneError::Invalid(format!("group {} is out of range, there are {} groups", group, MAX_GROUPS)))
+    }
+}
+
+// The listed groups, or all of them when there is no list
+fn group_mask(groups: &Option<Vec<u8>>) -> Result<GroupMask, SceneError> {
+    match groups {
+        Some(groups) => groups.iter().map(|&group| group_index(group)).collect(),
+        None => Ok(GroupMask::ALL),
+    }
+}
+
+// Square matrix whose row `a` lists which groups act on group `a`
+fn group_matrix(name: &str, rows: &[Vec<bool>]) -> Result<GroupMatrix, SceneError> {
+    if rows.len() > MAX_GROUPS || rows.iter().any(|row| row.len() != rows.len()) {
+        return Err(SceneError::Invalid(format!("{} must be square with at most {} rows", name, MAX_GROUPS)));
+    }
+    let mut matrix = GroupMatrix::NONE;
+    for (target, row) in rows.iter().enumerate() {
+        for (source, &enabled) in row.iter().enumerate() {
+            matrix.set(target as u8, source as u8, enabled);
+        }
+    }
+    Ok(matrix)
+}
+
+fn falloff_named(name: &str) -> Result<Falloff, SceneError> {
+    Ok(match name {
+        "constant" => Falloff::Constant,
+        "linear" => Falloff::Linear,
+        "inverse" => Falloff::Inverse,
+        "inverse_square" => Falloff::InverseSquare,
+        "smoothstep" => Falloff::Smoothstep,
+        _ => return Err(SceneError::Invalid(format!("unknown falloff `{}`", name))),
+    })
+}
+
+// Parse a "#rrggbb" color into 0x00rrggbb
+fn parse_color(text: &str) -> Result<u32, SceneError> {
+    text.strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| SceneError::Invalid(format!("color `{}` is not of the form #rrggbb", text)))
+}
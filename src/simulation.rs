@@ -1,119 +1,503 @@
-use crate::particle::Particle;
-use crate::force::{Force, Gravity, Wind, MouseGravity};
+use crate::interaction::{self, PairBody, PairForce, PairSolver};
+use crate::integrator::{Integrator, Rk4};
+use crate::particle::{limit_force, Particle, State, StateDerivative};
+use crate::store::ParticleStore;
+use crate::boundary::{Boundaries, BoundaryPolicy, WallHit};
+use crate::broadphase::{Aabb, BroadPhase, UniformGrid};
+use crate::emitter::{Emitter, SubEmitter, Trigger};
+use crate::force::{Force, ForceContext, InputState};
+use crate::group::{GroupMask, GroupMatrix};
+use crate::scene::{Bounds, Scene, DEFAULT_SCENE};
+use crate::modifier::Modifier;
 use nalgebra::Vector2;
-use rand::Rng;
-use std::time::Instant;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use rand::SeedableRng;
 
 pub struct Simulation {
-    pub particles: Vec<Particle>,
+    pub particles: ParticleStore,
     width: usize,
     height: usize,
-    forces: Vec<Box<dyn Force>>,
-    last_update: Instant,
+    boundaries: Boundaries,
+    forces: Vec<(Box<dyn Force>, GroupMask)>,
+    integrator: Box<dyn Integrator>,
+    broad_phase: Box<dyn BroadPhase>,
+    collision_pairs: Vec<(usize, usize)>,
+    contacts: Vec<(u64, u64)>, // Ids of touching pairs, sorted, from this step
+    previous_contacts: Vec<(u64, u64)>, // and the one before, so collisions trigger once per contact
+    collision_matrix: GroupMatrix,
+    pair_solver: PairSolver,
+    pair_forces: Vec<(Box<dyn PairForce>, GroupMatrix)>,
+    emitters: Vec<Emitter>,
+    modifiers: Vec<(Box<dyn Modifier>, GroupMask)>,
+    sub_emitters: Vec<SubEmitter>,
+    sub_emitter_depth: u8,
+    expired: Vec<Particle>, // Particles removed this step for reaching their lifetime
+    boundary_hits: Vec<(usize, WallHit)>, // Particles that reached a wall this step
+    triggered: Vec<(Trigger, Particle)>, // Sub-emitter triggers this step, with the parent as it was then
+    rng: StdRng, // Randomness while stepping, e.g. for emitters
+    input: InputState,
+    time: f32,
 }
 
 impl Simulation {
+    /// Creates a simulation with a randomly chosen seed.
     pub fn new(width: usize, height: usize) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut particles = Vec::new();
-        
-        // Create some initial particles
-        for _ in 0..50 {  // Reduced number of particles for better visibility
-            let x = rng.gen_range(0.0..width as f32);
-            let y = rng.gen_range(0.0..height as f32);
-            let mass = rng.gen_range(0.5..2.0);  // Lighter particles
-            let radius = mass * 2.0;
-            
-            // Create random color
-            let r = rng.gen_range(0..255);
-            let g = rng.gen_range(0..255);
-            let b = rng.gen_range(0..255);
-            let color = (r << 16) | (g << 8) | b;
-            
-            particles.push(Particle::new(x, y, mass, radius, color));
-        }
-        
-        // Set up forces
-        let mut forces: Vec<Box<dyn Force>> = Vec::new();
-        // Reduced regular gravity
-        forces.push(Box::new(Gravity {
-            strength: 2.0,  // Much weaker regular gravity
-            direction: Vector2::new(0.0, 1.0),
-        }));
-        forces.push(Box::new(MouseGravity::default()));
-        
+        Self::with_seed(width, height, rand::random())
+    }
+
+    /// Creates a simulation of the built-in scene ([`DEFAULT_SCENE`]) whose
+    /// initial particles are generated from `seed`. Two simulations built with
+    /// the same seed and stepped with the same sequence of timesteps and inputs
+    /// produce bit-identical particle states.
+    ///
+    /// Panics if either size is zero or larger than [`MAX_SIZE`](crate::renderer::MAX_SIZE).
+    pub fn with_seed(width: usize, height: usize, seed: u64) -> Self {
+        let mut scene: Scene = DEFAULT_SCENE.parse().expect("built-in scene is valid");
+        scene.bounds = Bounds { width, height };
+        scene.seed = Some(seed);
+        scene.build().expect("simulation bounds must be valid")
+    }
+
+    /// Creates a simulation with no particles and no forces.
+    pub fn empty(width: usize, height: usize) -> Self {
         Self {
-            particles,
+            particles: ParticleStore::new(),
             width,
             height,
-            forces,
-            last_update: Instant::now(),
+            boundaries: Boundaries::default(),
+            forces: Vec::new(),
+            integrator: Box::new(Rk4),
+            // Cells about the diameter of the largest initial particle
+            broad_phase: Box::new(UniformGrid::new(8.0)),
+            collision_pairs: Vec::new(),
+            contacts: Vec::new(),
+            previous_contacts: Vec::new(),
+            collision_matrix: GroupMatrix::ALL,
+            pair_solver: PairSolver::Direct,
+            pair_forces: Vec::new(),
+            emitters: Vec::new(),
+            modifiers: Vec::new(),
+            sub_emitters: Vec::new(),
+            sub_emitter_depth: 1,
+            expired: Vec::new(),
+            boundary_hits: Vec::new(),
+            triggered: Vec::new(),
+            rng: StdRng::seed_from_u64(0),
+            input: InputState::default(),
+            time: 0.0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Adds an external force applied to every particle.
+    pub fn add_force(&mut self, force: Box<dyn Force>) {
+        self.add_force_to_groups(force, GroupMask::ALL);
+    }
+
+    /// Adds an external force applied only to particles in `groups`.
+    pub fn add_force_to_groups(&mut self, force: Box<dyn Force>, groups: GroupMask) {
+        self.forces.push((force, groups));
+    }
+
+    /// Adds a force acting between every pair of particles.
+    pub fn add_pair_force(&mut self, pair_force: Box<dyn PairForce>) {
+        self.add_pair_force_between(pair_force, GroupMatrix::ALL);
+    }
+
+    /// Adds a force acting on particles of each group only from the groups in
+    /// its row of `groups`.
+    pub fn add_pair_force_between(&mut self, pair_force: Box<dyn PairForce>, groups: GroupMatrix) {
+        self.pair_forces.push((pair_force, groups));
+    }
+
+    /// Re-draws the random parameters of every pair force, such as Particle
+    /// Life rules, from `seed`.
+    pub fn randomize_pair_forces(&mut self, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        for (pair_force, _) in &mut self.pair_forces {
+            pair_force.randomize(&mut rng);
         }
     }
-    
-    pub fn update(&mut self, mouse_pos: Vector2<f32>) {
-        let now = Instant::now();
-        let dt = now.duration_since(self.last_update).as_secs_f32();
-        self.last_update = now;
 
-        // Limit dt to prevent instability with large time steps
-        let dt = dt.min(1.0 / 30.0);
+    /// Sets what happens to particles at each edge of the bounds. Defaults to
+    /// reflecting off every edge.
+    pub fn set_boundaries(&mut self, boundaries: Boundaries) {
+        self.boundaries = boundaries;
+    }
+
+    /// Selects which groups collide with each other. Defaults to all of them;
+    /// the matrix is expected to be symmetric.
+    pub fn set_collision_matrix(&mut self, collisions: GroupMatrix) {
+        self.collision_matrix = collisions;
+    }
+
+    /// Adds an emitter that spawns particles as the simulation runs.
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    pub fn emitters_mut(&mut self) -> &mut [Emitter] {
+        &mut self.emitters
+    }
+
+    /// Adds a sub-emitter that spawns children from particles when its trigger
+    /// happens to them.
+    pub fn add_sub_emitter(&mut self, sub_emitter: SubEmitter) {
+        self.sub_emitters.push(sub_emitter);
+    }
+
+    /// Sets how many levels of sub-emitters can nest. Particles of generation
+    /// `depth` or later don't fire sub-emitters, so with the default of 1 only
+    /// particles from populations and emitters have children.
+    pub fn set_sub_emitter_depth(&mut self, depth: u8) {
+        self.sub_emitter_depth = depth;
+    }
+
+    /// Adds a modifier that changes the appearance of particles in `groups`
+    /// over their lifetime. Modifiers run in the order they were added.
+    pub fn add_modifier(&mut self, modifier: Box<dyn Modifier>, groups: GroupMask) {
+        self.modifiers.push((modifier, groups));
+    }
+
+    /// Reseeds the random number generator used while stepping, so emitted
+    /// particles are reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Seconds of simulated time since the simulation was created.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Sets the input state forces can react to.
+    pub fn set_input(&mut self, input: InputState) {
+        self.input = input;
+    }
+
+    /// Sets the cursor position used by mouse-driven forces, or `None` when
+    /// there is no cursor (e.g. when running headless).
+    pub fn set_mouse_position(&mut self, mouse_pos: Option<Vector2<f32>>) {
+        self.input.mouse_position = mouse_pos;
+    }
+
+    /// Selects the scheme used to integrate particle motion. Defaults to [`Rk4`].
+    pub fn set_integrator(&mut self, integrator: Box<dyn Integrator>) {
+        self.integrator = integrator;
+    }
+
+    /// Selects the broad-phase used to find candidate particle-particle collisions.
+    /// Defaults to a [`UniformGrid`].
+    pub fn set_broad_phase(&mut self, broad_phase: Box<dyn BroadPhase>) {
+        self.broad_phase = broad_phase;
+    }
+
+    /// Selects how inter-particle forces without a cutoff are summed. Defaults to
+    /// [`PairSolver::Direct`]; use [`PairSolver::BarnesHut`] for large particle counts.
+    pub fn set_pair_solver(&mut self, pair_solver: PairSolver) {
+        self.pair_solver = pair_solver;
+    }
+
+    /// Advances the simulation by exactly `dt` seconds.
+    pub fn step(&mut self, dt: f32) {
+        // 1. Let time-dependent forces catch up with the simulation time
+        for (force, _) in &mut self.forces {
+            force.advance(self.time);
+        }
 
-        // 1. Apply external forces (e.g., Gravity, Wind)
-        for particle in &mut self.particles {
-            for force in &self.forces {
-                let force_vector = force.apply(&particle.position, &particle.velocity, particle.mass, Some(&mouse_pos));
-                particle.apply_force(force_vector);
+        // 2. Spawn particles from the emitters
+        for emitter in &mut self.emitters {
+            for _ in 0..emitter.due(self.time, dt) {
+                self.particles.push(emitter.spawn(&mut self.rng));
             }
         }
 
-        // 2. Apply inter-particle attraction/repulsion forces
-        crate::interaction::apply_attraction_repulsion(&mut self.particles);
+        // 3. Integrate, re-evaluating all forces at every stage the integrator needs
+        let mut states = self.particles.states();
+        self.integrator.integrate(&mut states, dt, &|offset, states| self.evaluate(offset, dt, states));
+
+        // 4. Update particles with their integrated states
+        self.particles.update(&states);
+
+        // 5. Age the particles and remove the expired ones, freeing their slots
+        self.particles.advance_ages(dt, &mut self.expired);
+        self.triggered.clear();
+        self.triggered.extend(self.expired.iter().map(|&particle| (Trigger::Death, particle)));
+
+        // 6. Update the appearance of the survivors from their new ages
+        self.particles.apply_modifiers(&self.modifiers);
 
-        // 3. Update particle positions using RK4 integration
-        for particle in &mut self.particles {
-            particle.update(dt);
+        // 7. Handle boundary collisions, removing particles absorbed by the walls
+        let bounds = Vector2::new(self.width as f32, self.height as f32);
+        self.particles.handle_boundary_collisions(&self.boundaries, bounds, &mut self.boundary_hits);
+        for &(index, hit) in &self.boundary_hits {
+            // Particles resting against a wall reach it every step without hitting it
+            let killed = hit.policy == BoundaryPolicy::Kill;
+            if killed || hit.speed >= self.boundaries.min_impact_speed {
+                self.triggered.extend(self.particles.get(index).map(|particle| (Trigger::Boundary, particle)));
+            }
+            if killed {
+                self.particles.remove(index);
+            }
+        }
+
+        // 8. Handle particle-particle collisions between broad-phase candidates.
+        // Contacts are resolved sequentially: a particle can touch several others,
+        // and each impulse depends on the ones resolved before it.
+        let particles = &self.particles;
+        let boxes: Vec<Aabb> = (0..particles.len()).into_par_iter()
+            .map(|i| Aabb::around(particles.position(i), particles.radii()[i]))
+            .collect();
+        self.broad_phase.find_pairs(&boxes, &mut self.collision_pairs);
+        let groups = self.particles.groups();
+        let collision_matrix = self.collision_matrix;
+        self.collision_pairs.retain(|&(i, j)| collision_matrix.get(groups[i], groups[j]));
+        std::mem::swap(&mut self.contacts, &mut self.previous_contacts);
+        self.contacts.clear();
+        for &(i, j) in &self.collision_pairs {
+            let (Some(mut particle1), Some(mut particle2)) = (self.particles.get(i), self.particles.get(j)) else {
+                continue;
+            };
+            if (particle1.position - particle2.position).norm() >= particle1.radius + particle2.radius {
+                continue;
+            }
+            // Keyed by id, so a particle pushed into a freed slot starts new contacts
+            let contact = (self.particles.ids()[i], self.particles.ids()[j]);
+            self.contacts.push(contact);
+            // A contact triggers sub-emitters only when it starts, and not for a
+            // particle touching a later generation, e.g. children spawned on top of it
+            let new_contact = self.previous_contacts.binary_search(&contact).is_err();
+            if particle1.handle_collision(&mut particle2) && new_contact {
+                if particle2.generation <= particle1.generation {
+                    self.triggered.push((Trigger::Collision, particle1));
+                }
+                if particle1.generation <= particle2.generation {
+                    self.triggered.push((Trigger::Collision, particle2));
+                }
+            }
+            self.particles.set(i, particle1);
+            self.particles.set(j, particle2);
         }
+        self.contacts.sort_unstable();
 
-        // 4. Handle boundary collisions
-        let sim_width = self.width as f32;
-        let sim_height = self.height as f32;
-        for particle in &mut self.particles {
-            particle.handle_boundary_collision(sim_width, sim_height);
+        // 9. Fire sub-emitters for this step's deaths, wall hits and collisions.
+        // Children start moving next step.
+        for (trigger, parent) in &self.triggered {
+            if parent.generation >= self.sub_emitter_depth {
+                continue;
+            }
+            for sub_emitter in &self.sub_emitters {
+                if sub_emitter.trigger == *trigger && sub_emitter.parents.contains(parent.group) {
+                    for _ in 0..sub_emitter.count {
+                        self.particles.push(sub_emitter.spawn(parent, &mut self.rng));
+                    }
+                }
+            }
         }
 
-        // 5. Handle particle-particle collisions
-        let len = self.particles.len();
-        for i in 0..len {
-            for j in (i+1)..len {
-                let (particles_a, particles_b) = self.particles.split_at_mut(j);
-                let particle1 = &mut particles_a[i];
-                let particle2 = &mut particles_b[0];
-                particle1.handle_collision(particle2);
+        self.time += dt;
+    }
+
+    // Evaluate the derivative of every particle at the given states, `offset`
+    // seconds into a step of length `dt`. External forces and inter-particle
+    // forces are sampled at `states`, so position- and velocity-dependent forces
+    // see each intermediate integrator stage. Forces applied to a particle
+    // before the step are held constant across it.
+    fn evaluate(&self, offset: f32, dt: f32, states: &[State]) -> Vec<StateDerivative> {
+        let mut forces = vec![Vector2::new(0.0, 0.0); states.len()];
+
+        // External forces (e.g., Gravity, Wind), one particle per task
+        let external_forces = &self.forces;
+        let masses = self.particles.masses();
+        let charges = self.particles.charges();
+        let radii = self.particles.radii();
+        let groups = self.particles.groups();
+        let alive = self.particles.alive();
+        let time = self.time + offset;
+        let input = &self.input;
+        let bounds = Vector2::new(self.width as f32, self.height as f32);
+        forces.par_iter_mut().zip(states).enumerate().for_each(|(index, (force_sum, state))| {
+            if !alive[index] {
+                return;
+            }
+            let ctx = ForceContext {
+                time,
+                dt,
+                index,
+                position: state.position,
+                velocity: state.velocity,
+                mass: masses[index],
+                charge: charges[index],
+                radius: radii[index],
+                group: groups[index],
+                input,
+                bounds,
+                states,
+            };
+            for (force, mask) in external_forces {
+                if mask.contains(ctx.group) {
+                    *force_sum += limit_force(force.apply(&ctx));
+                }
+            }
+        });
+
+        // Inter-particle forces, between live particles only
+        if !self.pair_forces.is_empty() {
+            let live: Vec<usize> = (0..states.len()).filter(|&i| alive[i]).collect();
+            let bodies: Vec<PairBody> = live.iter().map(|&i| PairBody {
+                position: states[i].position,
+                velocity: states[i].velocity,
+                mass: masses[i],
+                charge: charges[i],
+                radius: radii[i],
+                group: groups[i],
+            }).collect();
+            let mut live_forces: Vec<Vector2<f32>> = live.iter().map(|&i| forces[i]).collect();
+            interaction::apply_pair_forces(&self.pair_forces, &bodies, self.pair_solver, &mut live_forces);
+            for (&i, force) in live.iter().zip(live_forces) {
+                forces[i] = force;
             }
         }
+
+        let particles = &self.particles;
+        forces.into_par_iter().zip(states).zip(masses).enumerate().map(|(i, ((force, state), &mass))| {
+            StateDerivative {
+                dp: state.velocity,
+                dv: force / mass + particles.acceleration(i),
+            }
+        }).collect()
     }
 }
 
-// Private helper function for boundary collision handling.
-fn handle_boundary_collision_particle(width: f32, height: f32, particle: &mut Particle) {
-    let restitution = 0.8; // Bounciness factor
-    
-    // Left and right boundaries
-    if particle.position.x - particle.radius < 0.0 {
-        particle.position.x = particle.radius;
-        particle.velocity.x = -particle.velocity.x * restitution;
-    } else if particle.position.x + particle.radius > width {
-        particle.position.x = width - particle.radius;
-        particle.velocity.x = -particle.velocity.x * restitution;
-    }
-    
-    // Top and bottom boundaries
-    if particle.position.y - particle.radius < 0.0 {
-        particle.position.y = particle.radius;
-        particle.velocity.y = -particle.velocity.y * restitution;
-    } else if particle.position.y + particle.radius > height {
-        particle.position.y = height - particle.radius;
-        particle.velocity.y = -particle.velocity.y * restitution;
-    }
-} 
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emitter::EmitterShape;
+    use crate::force::Gravity;
+
+    // Every attribute of every slot, as raw bits
+    fn state_bits(simulation: &Simulation) -> Vec<u32> {
+        let particles = &simulation.particles;
+        let columns = [
+            particles.position_x(),
+            particles.position_y(),
+            particles.velocity_x(),
+            particles.velocity_y(),
+            particles.masses(),
+            particles.radii(),
+            particles.alphas(),
+            particles.ages(),
+        ];
+        let mut bits: Vec<u32> = columns.iter().flat_map(|column| column.iter().map(|value| value.to_bits())).collect();
+        bits.extend(particles.colors());
+        bits.extend(particles.alive().iter().map(|&alive| alive as u32));
+        bits
+    }
+
+    #[test]
+    fn same_seed_gives_bit_identical_states() {
+        let mut a = Simulation::with_seed(800, 600, 42);
+        let mut b = Simulation::with_seed(800, 600, 42);
+        for step in 0..60 {
+            let mouse = (step % 20 < 10).then(|| Vector2::new(step as f32 * 3.0, 300.0));
+            for simulation in [&mut a, &mut b] {
+                simulation.set_mouse_position(mouse);
+                simulation.step(1.0 / 120.0);
+            }
+        }
+        assert_eq!(state_bits(&a), state_bits(&b));
+        assert_ne!(state_bits(&a), state_bits(&Simulation::with_seed(800, 600, 43)));
+    }
+
+    #[test]
+    fn emitted_particles_are_reproducible() {
+        let mut scene: Scene = include_str!("../scenes/fireworks.toml").parse().unwrap();
+        scene.seed = Some(7);
+        let (mut a, mut b) = (scene.build().unwrap(), scene.build().unwrap());
+        for _ in 0..240 {
+            a.step(1.0 / 120.0);
+            b.step(1.0 / 120.0);
+        }
+        assert!(a.particles.live_count() > 1);
+        assert_eq!(state_bits(&a), state_bits(&b));
+    }
+
+    #[test]
+    fn collision_sub_emitter_fires_once_per_contact() {
+        let mut simulation = Simulation::empty(800, 600);
+        for (x, vx) in [(390.0, 100.0), (410.0, -100.0)] {
+            let mut particle = Particle::new(x, 300.0, 1.0, 5.0, 0xffffff);
+            particle.velocity = Vector2::new(vx, 0.0);
+            simulation.particles.push(particle);
+        }
+        let mut emitter = Emitter::new(Vector2::new(0.0, 0.0));
+        emitter.shape = EmitterShape::Circle { radius: 3.0 };
+        let mut sub_emitter = SubEmitter::new(Trigger::Collision, emitter);
+        sub_emitter.count = 3;
+        simulation.add_sub_emitter(sub_emitter);
+
+        for _ in 0..240 {
+            simulation.step(1.0 / 120.0);
+        }
+        // Each parent fires once; touching its own children doesn't fire again
+        assert_eq!(simulation.particles.live_count(), 2 + 2 * 3);
+    }
+
+    #[test]
+    fn contacts_do_not_carry_over_to_reused_slots() {
+        let mut simulation = Simulation::empty(800, 600);
+        let mut collisions = GroupMatrix::NONE;
+        collisions.set(0, 0, true);
+        simulation.set_collision_matrix(collisions);
+        for (x, vx) in [(400.0, 20.0), (408.0, -20.0)] {
+            let mut particle = Particle::new(x, 300.0, 1.0, 5.0, 0xffffff);
+            particle.velocity = Vector2::new(vx, 0.0);
+            simulation.particles.push(particle);
+        }
+        let mut emitter = Emitter::new(Vector2::new(0.0, 0.0));
+        emitter.group = 1;
+        simulation.add_sub_emitter(SubEmitter::new(Trigger::Collision, emitter));
+
+        simulation.step(1.0 / 120.0);
+        assert_eq!(simulation.particles.live_count(), 4);
+
+        // A new particle in the second one's slot, still touching the first
+        let index = 1;
+        let mut particle = simulation.particles.get(index).unwrap();
+        particle.velocity = Vector2::new(-100.0, 0.0);
+        simulation.particles.remove(index);
+        assert_eq!(simulation.particles.push(particle), index);
+        simulation.step(1.0 / 120.0);
+        assert_eq!(simulation.particles.live_count(), 6);
+    }
+
+    #[test]
+    fn boundary_sub_emitter_ignores_resting_particles() {
+        let mut simulation = Simulation::empty(800, 600);
+        simulation.set_boundaries(Boundaries::all(BoundaryPolicy::Stick));
+        simulation.add_force(Box::new(Gravity {
+            strength: 150.0,
+            direction: Vector2::new(0.0, 1.0),
+        }));
+        simulation.particles.push(Particle::new(400.0, 500.0, 1.0, 5.0, 0xffffff));
+        let mut emitter = Emitter::new(Vector2::new(0.0, 0.0));
+        emitter.group = 1;
+        let mut sub_emitter = SubEmitter::new(Trigger::Boundary, emitter);
+        sub_emitter.parents = GroupMask::only(0);
+        simulation.add_sub_emitter(sub_emitter);
+
+        for _ in 0..240 {
+            simulation.step(1.0 / 120.0);
+        }
+        // Landing fires once; lying on the floor afterwards doesn't
+        assert_eq!(simulation.particles.live_count(), 2);
+    }
+}
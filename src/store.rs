@@ -0,0 +1,362 @@
+use crate::boundary::{Boundaries, WallHit};
+use crate::group::GroupMask;
+use crate::modifier::{Appearance, Modifier};
+use crate::particle::{limit_velocity, Particle, State};
+use nalgebra::Vector2;
+use rayon::prelude::*;
+
+/// Structure-of-arrays particle storage.
+///
+/// Every particle attribute lives in its own contiguous array, so passes that
+/// only touch a few attributes stay cache-friendly and vectorize well.
+/// Individual particles are read and written as [`Particle`] values through
+/// [`get`](Self::get), [`set`](Self::set) and [`iter`](Self::iter).
+///
+/// Storage is pooled: [`remove`](Self::remove) frees a particle's slot and
+/// the next [`push`](Self::push) reuses it, so indices of the other particles
+/// stay put and sustained spawning and expiry doesn't reallocate.
+#[derive(Clone, Debug, Default)]
+pub struct ParticleStore {
+    position_x: Vec<f32>,
+    position_y: Vec<f32>,
+    velocity_x: Vec<f32>,
+    velocity_y: Vec<f32>,
+    acceleration_x: Vec<f32>,
+    acceleration_y: Vec<f32>,
+    mass: Vec<f32>,
+    charge: Vec<f32>,
+    radius: Vec<f32>,
+    color: Vec<u32>,
+    alpha: Vec<f32>,
+    group: Vec<u8>,
+    age: Vec<f32>,
+    lifetime: Vec<f32>,
+    generation: Vec<u8>,
+    restitution: Vec<f32>,
+    spawn_radius: Vec<f32>, // Radius when pushed, the base for size modifiers
+    id: Vec<u64>,
+    alive: Vec<bool>,
+    free: Vec<usize>, // Slots of removed particles, reused by `push`
+    next_id: u64,
+}
+
+impl ParticleStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of slots, live or free. Particle indices range over `0..len()`.
+    pub fn len(&self) -> usize {
+        self.mass.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mass.is_empty()
+    }
+
+    /// Number of live particles.
+    pub fn live_count(&self) -> usize {
+        self.len() - self.free.len()
+    }
+
+    pub fn is_alive(&self, index: usize) -> bool {
+        self.alive.get(index).copied().unwrap_or(false)
+    }
+
+    /// Adds a particle, reusing a free slot if there is one, and returns its index.
+    pub fn push(&mut self, particle: Particle) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(index) = self.free.pop() {
+            self.set(index, particle);
+            self.spawn_radius[index] = particle.radius;
+            self.id[index] = id;
+            self.alive[index] = true;
+            return index;
+        }
+        self.position_x.push(particle.position.x);
+        self.position_y.push(particle.position.y);
+        self.velocity_x.push(particle.velocity.x);
+        self.velocity_y.push(particle.velocity.y);
+        self.acceleration_x.push(particle.acceleration.x);
+        self.acceleration_y.push(particle.acceleration.y);
+        self.mass.push(particle.mass);
+        self.charge.push(particle.charge);
+        self.radius.push(particle.radius);
+        self.color.push(particle.color);
+        self.alpha.push(particle.alpha);
+        self.group.push(particle.group);
+        self.age.push(particle.age);
+        self.lifetime.push(particle.lifetime);
+        self.generation.push(particle.generation);
+        self.restitution.push(particle.restitution);
+        self.spawn_radius.push(particle.radius);
+        self.id.push(id);
+        self.alive.push(true);
+        self.len() - 1
+    }
+
+    /// Removes the particle at `index`, freeing its slot for reuse. Returns
+    /// `false` if there was no live particle there.
+    pub fn remove(&mut self, index: usize) -> bool {
+        if !self.is_alive(index) {
+            return false;
+        }
+        self.alive[index] = false;
+        // Park the slot so it stays inert until reused
+        self.velocity_x[index] = 0.0;
+        self.velocity_y[index] = 0.0;
+        self.acceleration_x[index] = 0.0;
+        self.acceleration_y[index] = 0.0;
+        self.free.push(index);
+        true
+    }
+
+    /// Reads the particle at `index`, or `None` if there is no live particle there.
+    pub fn get(&self, index: usize) -> Option<Particle> {
+        if !self.is_alive(index) {
+            return None;
+        }
+        Some(Particle {
+            position: Vector2::new(self.position_x[index], self.position_y[index]),
+            velocity: Vector2::new(self.velocity_x[index], self.velocity_y[index]),
+            acceleration: Vector2::new(self.acceleration_x[index], self.acceleration_y[index]),
+            mass: self.mass[index],
+            charge: self.charge[index],
+            radius: self.radius[index],
+            color: self.color[index],
+            alpha: self.alpha[index],
+            group: self.group[index],
+            age: self.age[index],
+            lifetime: self.lifetime[index],
+            generation: self.generation[index],
+            restitution: self.restitution[index],
+        })
+    }
+
+    /// Overwrites the particle at `index`.
+    ///
+    /// Panics if `index` is out of range.
+    pub fn set(&mut self, index: usize, particle: Particle) {
+        self.position_x[index] = particle.position.x;
+        self.position_y[index] = particle.position.y;
+        self.velocity_x[index] = particle.velocity.x;
+        self.velocity_y[index] = particle.velocity.y;
+        self.acceleration_x[index] = particle.acceleration.x;
+        self.acceleration_y[index] = particle.acceleration.y;
+        self.mass[index] = particle.mass;
+        self.charge[index] = particle.charge;
+        self.radius[index] = particle.radius;
+        self.color[index] = particle.color;
+        self.alpha[index] = particle.alpha;
+        self.group[index] = particle.group;
+        self.age[index] = particle.age;
+        self.lifetime[index] = particle.lifetime;
+        self.generation[index] = particle.generation;
+        self.restitution[index] = particle.restitution;
+    }
+
+    /// Reads, modifies and writes back the particle at `index`.
+    ///
+    /// Panics if there is no live particle at `index`.
+    pub fn modify<R>(&mut self, index: usize, f: impl FnOnce(&mut Particle) -> R) -> R {
+        let mut particle = self.get(index).expect("particle index out of range");
+        let result = f(&mut particle);
+        self.set(index, particle);
+        result
+    }
+
+    /// Live particles, in index order.
+    pub fn iter(&self) -> impl Iterator<Item = Particle> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+
+    pub fn position(&self, index: usize) -> Vector2<f32> {
+        Vector2::new(self.position_x[index], self.position_y[index])
+    }
+
+    pub fn velocity(&self, index: usize) -> Vector2<f32> {
+        Vector2::new(self.velocity_x[index], self.velocity_y[index])
+    }
+
+    pub fn acceleration(&self, index: usize) -> Vector2<f32> {
+        Vector2::new(self.acceleration_x[index], self.acceleration_y[index])
+    }
+
+    // Column accessors for bulk passes
+
+    pub fn position_x(&self) -> &[f32] {
+        &self.position_x
+    }
+
+    pub fn position_y(&self) -> &[f32] {
+        &self.position_y
+    }
+
+    pub fn velocity_x(&self) -> &[f32] {
+        &self.velocity_x
+    }
+
+    pub fn velocity_y(&self) -> &[f32] {
+        &self.velocity_y
+    }
+
+    pub fn masses(&self) -> &[f32] {
+        &self.mass
+    }
+
+    pub fn charges(&self) -> &[f32] {
+        &self.charge
+    }
+
+    pub fn radii(&self) -> &[f32] {
+        &self.radius
+    }
+
+    pub fn colors(&self) -> &[u32] {
+        &self.color
+    }
+
+    pub fn alphas(&self) -> &[f32] {
+        &self.alpha
+    }
+
+    pub fn groups(&self) -> &[u8] {
+        &self.group
+    }
+
+    pub fn ages(&self) -> &[f32] {
+        &self.age
+    }
+
+    pub fn lifetimes(&self) -> &[f32] {
+        &self.lifetime
+    }
+
+    pub fn generations(&self) -> &[u8] {
+        &self.generation
+    }
+
+    /// Identifier of the particle in each slot. Every push gets a new one, so
+    /// it tells a particle apart from an earlier one whose slot it reused.
+    pub fn ids(&self) -> &[u64] {
+        &self.id
+    }
+
+    /// Whether each slot holds a live particle.
+    pub fn alive(&self) -> &[bool] {
+        &self.alive
+    }
+
+    /// Kinematic state of every particle, in index order.
+    pub fn states(&self) -> Vec<State> {
+        (0..self.len()).map(|index| State {
+            position: self.position(index),
+            velocity: self.velocity(index),
+        }).collect()
+    }
+
+    // Bulk equivalents of the per-particle operations on `Particle`
+
+    // Moves every particle to its integrated state, see `Particle::update`
+    pub(crate) fn update(&mut self, states: &[State]) {
+        self.position_x.par_iter_mut()
+            .zip(&mut self.position_y)
+            .zip(&mut self.velocity_x)
+            .zip(&mut self.velocity_y)
+            .zip(states)
+            .for_each(|((((x, y), vx), vy), state)| {
+                let velocity = limit_velocity(state.velocity);
+                *x = state.position.x;
+                *y = state.position.y;
+                *vx = velocity.x;
+                *vy = velocity.y;
+            });
+        self.acceleration_x.fill(0.0);
+        self.acceleration_y.fill(0.0);
+    }
+
+    // Keeps every live particle inside the bounds, see `Particle::handle_boundary_collision`,
+    // collecting the indices of those that reached a wall and the hit into `hits` in index order
+    pub(crate) fn handle_boundary_collisions(
+        &mut self,
+        boundaries: &Boundaries,
+        bounds: Vector2<f32>,
+        hits: &mut Vec<(usize, WallHit)>,
+    ) {
+        hits.clear();
+        hits.par_extend(self.position_x.par_iter_mut()
+            .zip(&mut self.position_y)
+            .zip(&mut self.velocity_x)
+            .zip(&mut self.velocity_y)
+            .zip(&self.radius)
+            .zip(&self.restitution)
+            .zip(&self.alive)
+            .enumerate()
+            .filter(|(_, (_, &alive))| alive)
+            .filter_map(|(index, ((((((x, y), vx), vy), &radius), &restitution), _))| {
+                let mut position = Vector2::new(*x, *y);
+                let mut velocity = Vector2::new(*vx, *vy);
+                let hit = boundaries.apply(&mut position, &mut velocity, radius, restitution, bounds);
+                *x = position.x;
+                *y = position.y;
+                *vx = velocity.x;
+                *vy = velocity.y;
+                hit.map(|hit| (index, hit))
+            }));
+    }
+
+    // Ages every live particle by `dt` and removes those past their lifetime,
+    // collecting them as they were when they died into `expired` in index order
+    pub(crate) fn advance_ages(&mut self, dt: f32, expired: &mut Vec<Particle>) {
+        expired.clear();
+        for index in 0..self.len() {
+            if self.alive[index] {
+                self.age[index] += dt;
+                if self.age[index] >= self.lifetime[index] {
+                    expired.extend(self.get(index));
+                    self.remove(index);
+                }
+            }
+        }
+    }
+
+    // Applies the modifiers, in order, to every live mortal particle in their
+    // groups at its normalized age
+    pub(crate) fn apply_modifiers(&mut self, modifiers: &[(Box<dyn Modifier>, GroupMask)]) {
+        if modifiers.is_empty() {
+            return;
+        }
+        for index in 0..self.len() {
+            let lifetime = self.lifetime[index];
+            if !self.alive[index] || !lifetime.is_finite() {
+                continue;
+            }
+            let age = (self.age[index] / lifetime).clamp(0.0, 1.0);
+            let mut appearance = Appearance {
+                color: self.color[index],
+                alpha: self.alpha[index],
+                radius: self.radius[index],
+                spawn_radius: self.spawn_radius[index],
+            };
+            for (modifier, groups) in modifiers {
+                if groups.contains(self.group[index]) {
+                    modifier.apply(age, &mut appearance);
+                }
+            }
+            self.color[index] = appearance.color;
+            self.alpha[index] = appearance.alpha;
+            self.radius[index] = appearance.radius;
+        }
+    }
+}
+
+impl FromIterator<Particle> for ParticleStore {
+    fn from_iter<I: IntoIterator<Item = Particle>>(iter: I) -> Self {
+        let mut store = Self::new();
+        for particle in iter {
+            store.push(particle);
+        }
+        store
+    }
+}